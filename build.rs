@@ -57,4 +57,28 @@ fn main() {
         compile_shader(&mut compiler, shader);
         println!("cargo:rerun-if-changed={}", shader);
     }
+
+    {
+        let shader = "src/vkrs/shaders/tonemap.vert";
+        compile_shader(&mut compiler, shader);
+        println!("cargo:rerun-if-changed={}", shader);
+    }
+
+    {
+        let shader = "src/vkrs/shaders/tonemap.frag";
+        compile_shader(&mut compiler, shader);
+        println!("cargo:rerun-if-changed={}", shader);
+    }
+
+    {
+        let shader = "src/vkrs/shaders/overlay.vert";
+        compile_shader(&mut compiler, shader);
+        println!("cargo:rerun-if-changed={}", shader);
+    }
+
+    {
+        let shader = "src/vkrs/shaders/overlay.frag";
+        compile_shader(&mut compiler, shader);
+        println!("cargo:rerun-if-changed={}", shader);
+    }
 }
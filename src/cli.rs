@@ -0,0 +1,450 @@
+use crate::config::{self, Layer};
+use vkrs::{
+    AppConfig, BenchConfig, DeviceSelector, FrameSyncMode, MonitorSelector, PresentModePreference,
+    RedrawMode, SwapchainPreferences, ValidationConfig, VideoModeRequest, WindowMode,
+};
+
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: vkrs [info [--json] | bench [--frames <count>] [--warmup <count>]] [--config <file>]
+            [--scene <file>] [--width <pixels>] [--height <pixels>] [--window <mode>]
+            [--monitor <selector>] [--video-mode <mode>] [--gpu <selector>] [--vsync <mode>]
+            [--hdr <mode>] [--swapchain-images <count>] [--frames-in-flight <count>]
+            [--msaa <samples>] [--redraw <mode>] [--frame-sync <mode>]
+            [--validation <config>] [--validation-suppress <ids>] [--log <filter>]
+
+Commands:
+    info            Print what the engine sees for every physical device and exit
+    bench           Render with a fixed timestep and vsync off, print a JSON report of frame
+                    times, GPU time per pass and memory use, and exit
+
+Options:
+    --json          Print the device info as JSON instead of text
+    --frames <count>
+                    Number of frames the benchmark measures (default 1000)
+    --warmup <count>
+                    Number of frames rendered before the benchmark measures, may be 0
+                    (default 100)
+    --config <file> Read settings from this TOML file instead of VKRS_CONFIG or ./vkrs.toml
+    --scene <file>  Render the scene in a JSON file instead of the built-in quad
+    --width <pixels>, --height <pixels>
+                    Size of the window (default 800 by 600)
+    --window <mode> Open the window windowed (default), maximized, or fullscreen as a borderless
+                    window or exclusively. Press Alt+Enter while running to toggle fullscreen
+    --monitor <selector>
+                    Go fullscreen on a monitor by index or name substring instead of the one the
+                    window is on
+    --video-mode <mode>
+                    Video mode for exclusive fullscreen such as 1920x1080 or 1920x1080@144
+                    (default the largest with the highest refresh rate)
+    --gpu <selector>
+                    Force a device by index, UUID or name substring
+    --vsync <mode>  Present with vsync on, off, adaptive or low-latency (default). Press V while
+                    running to cycle through them
+    --hdr <mode>    Present in HDR with hdr10 or scrgb if the display supports it, or off
+                    (default)
+    --swapchain-images <count>
+                    Number of swapchain images, 2 for double and 3 for triple buffering. Press B
+                    while running to switch between them
+    --frames-in-flight <count>
+                    Number of frames the CPU may record ahead of the GPU (default 2). Press F
+                    while running to cycle through them
+    --msaa <samples>
+                    Render the scene with 1 (default, no MSAA), 2, 4 or 8 samples per pixel
+    --redraw <mode> Render continuous (default), or on-demand only when the window or a setting
+                    changed
+    --frame-sync <mode>
+                    Track the completion of frames with fences (default), or with a timeline
+                    semaphore if the device supports it
+    --validation <config>
+                    Validation off, on, or a comma separated list of gpu-assisted,
+                    best-practices, sync, debug-printf and fail-on-error to enable validation
+                    with those features. Defaults to on in debug builds and off in release
+                    builds
+    --validation-suppress <ids>
+                    Comma separated message ID names or hexadecimal numbers of validation
+                    messages to ignore
+    --log <filter>  Log filter such as vkrs=info,vulkan=warn (default errors only)
+    -h, --help      Print this help and exit
+
+Options override the environment variables listed in the README, which override the config file.";
+
+/// Width and height of the window when none is configured.
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (800, 600);
+
+pub enum Command {
+    Run,
+    Info { json: bool },
+    Bench(BenchConfig),
+}
+
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub mode: WindowMode,
+    pub monitor: Option<MonitorSelector>,
+    pub video_mode: Option<VideoModeRequest>,
+}
+
+pub struct Args {
+    pub command: Command,
+    /// The config file the settings were read from, if any.
+    pub config_file: Option<PathBuf>,
+    pub window: WindowConfig,
+    pub log_filter: Option<String>,
+    pub scene: Option<PathBuf>,
+    pub device_selector: Option<DeviceSelector>,
+    pub swapchain_preferences: SwapchainPreferences,
+    pub frames_in_flight: u32,
+    pub msaa_samples: u32,
+    pub redraw_mode: RedrawMode,
+    pub frame_sync: FrameSyncMode,
+    pub validation: ValidationConfig,
+}
+
+/// The command line before it has been merged with the environment and the config file.
+struct CommandLine {
+    command: Command,
+    config_file: Option<PathBuf>,
+    layer: Layer,
+}
+
+impl Args {
+    /// Parses the command line and merges it with the environment and the config file, printing
+    /// the usage and exiting on `--help` or invalid arguments.
+    pub fn parse() -> Self {
+        let command_line = match CommandLine::try_parse(std::env::args().skip(1)) {
+            Ok(command_line) => command_line,
+            Err(None) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(Some(error)) => {
+                eprintln!("{}\n\n{}", error, USAGE);
+                std::process::exit(2);
+            }
+        };
+        Self::resolve(command_line).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        })
+    }
+
+    /// Stacks the command line over the environment over the config file, and fills in the
+    /// defaults for whatever none of them set.
+    fn resolve(command_line: CommandLine) -> Result<Self, String> {
+        let config_file = config::config_file_path(command_line.config_file);
+        let file = match &config_file {
+            Some(path) => Layer::from_file(path)?,
+            None => Layer::default(),
+        };
+        let layer = command_line.layer.or(Layer::from_env()?).or(file);
+
+        let defaults = AppConfig::default();
+        let mut swapchain_preferences = SwapchainPreferences {
+            present_mode: layer.present_mode.unwrap_or_default(),
+            hdr: layer.hdr.unwrap_or_default(),
+            image_count: layer.swapchain_images,
+        };
+        if let Command::Bench(_) = command_line.command {
+            // Vsync would cap the frame rate at the refresh rate of the display.
+            swapchain_preferences.present_mode = PresentModePreference::Off;
+        }
+        let mut validation = layer.validation.unwrap_or_default();
+        validation.suppressed_message_ids = layer.suppressed_message_ids.unwrap_or_default();
+        Ok(Self {
+            command: command_line.command,
+            config_file,
+            window: WindowConfig {
+                width: layer.width.unwrap_or(DEFAULT_WINDOW_SIZE.0),
+                height: layer.height.unwrap_or(DEFAULT_WINDOW_SIZE.1),
+                mode: layer.window_mode.unwrap_or_default(),
+                monitor: layer.monitor,
+                video_mode: layer.video_mode,
+            },
+            log_filter: layer.log_filter,
+            scene: layer.scene,
+            device_selector: layer.device_selector,
+            swapchain_preferences,
+            frames_in_flight: layer
+                .frames_in_flight
+                .unwrap_or(defaults.renderer.frames_in_flight),
+            msaa_samples: layer.msaa_samples.unwrap_or(defaults.renderer.msaa_samples),
+            redraw_mode: layer.redraw_mode.unwrap_or(defaults.redraw_mode),
+            frame_sync: layer.frame_sync.unwrap_or(defaults.renderer.frame_sync),
+            validation,
+        })
+    }
+}
+
+impl CommandLine {
+    /// Returns `Err(None)` when help was requested.
+    fn try_parse(mut args: impl Iterator<Item = String>) -> Result<Self, Option<String>> {
+        let mut info = false;
+        let mut json = false;
+        let mut bench = false;
+        let mut frames = None;
+        let mut warmup = None;
+        let mut config_file = None;
+        let mut layer = Layer::default();
+        while let Some(arg) = args.next() {
+            // Options take their value either as `--option value` or as `--option=value`.
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || match inline_value {
+                Some(value) => Ok(value.to_owned()),
+                None => args
+                    .next()
+                    .ok_or_else(|| Some(format!("Missing value for {}", option))),
+            };
+            match option {
+                "info" if !info && !bench => info = true,
+                "bench" if !info && !bench => bench = true,
+                "--json" if inline_value.is_none() => json = true,
+                "--frames" => frames = Some(config::parse_count(option, &value()?)?),
+                "--warmup" => warmup = Some(parse_number(option, &value()?)?),
+                "--config" => config_file = Some(PathBuf::from(value()?)),
+                "--scene" => layer.scene = Some(PathBuf::from(value()?)),
+                "--width" => layer.width = Some(config::parse_size(option, &value()?)?),
+                "--height" => layer.height = Some(config::parse_size(option, &value()?)?),
+                "--window" => {
+                    layer.window_mode = Some(config::parse_window_mode(option, &value()?)?)
+                }
+                "--monitor" => layer.monitor = Some(MonitorSelector::parse(&value()?)),
+                "--video-mode" => {
+                    layer.video_mode = Some(config::parse_video_mode(option, &value()?)?)
+                }
+                "--gpu" => layer.device_selector = Some(DeviceSelector::parse(&value()?)),
+                "--vsync" => {
+                    layer.present_mode = Some(config::parse_present_mode(option, &value()?)?)
+                }
+                "--hdr" => layer.hdr = Some(config::parse_hdr_mode(option, &value()?)?),
+                "--swapchain-images" => {
+                    layer.swapchain_images = Some(config::parse_count(option, &value()?)?)
+                }
+                "--frames-in-flight" => {
+                    layer.frames_in_flight = Some(config::parse_count(option, &value()?)?)
+                }
+                "--msaa" => layer.msaa_samples = Some(config::parse_msaa(option, &value()?)?),
+                "--redraw" => {
+                    layer.redraw_mode = Some(config::parse_redraw_mode(option, &value()?)?)
+                }
+                "--frame-sync" => {
+                    layer.frame_sync = Some(config::parse_frame_sync(option, &value()?)?)
+                }
+                "--validation" => {
+                    layer.validation = Some(config::parse_validation(option, &value()?)?)
+                }
+                "--validation-suppress" => {
+                    layer.suppressed_message_ids =
+                        Some(ValidationConfig::parse_message_ids(&value()?))
+                }
+                "--log" => layer.log_filter = Some(value()?),
+                "-h" | "--help" => return Err(None),
+                _ => return Err(Some(format!("Unknown argument {:?}", arg))),
+            }
+        }
+
+        if json && !info {
+            return Err(Some(
+                "--json is only supported by the info command".to_owned(),
+            ));
+        }
+        if (frames.is_some() || warmup.is_some()) && !bench {
+            return Err(Some(
+                "--frames and --warmup are only supported by the bench command".to_owned(),
+            ));
+        }
+        let command = if info {
+            Command::Info { json }
+        } else if bench {
+            let defaults = BenchConfig::default();
+            Command::Bench(BenchConfig {
+                frames: frames.unwrap_or(defaults.frames),
+                warmup: warmup.unwrap_or(defaults.warmup),
+            })
+        } else {
+            Command::Run
+        };
+        Ok(Self {
+            command,
+            config_file,
+            layer,
+        })
+    }
+}
+
+fn parse_number(option: &str, value: &str) -> Result<u32, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "Invalid value {:?} for {}, expected a number",
+            value, option
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CommandLine, Option<String>> {
+        CommandLine::try_parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(Some(error)) => error,
+            Err(None) => panic!("{:?} requested help", args),
+            Ok(_) => panic!("{:?} was accepted", args),
+        }
+    }
+
+    #[test]
+    fn parses_run_without_arguments() {
+        let command_line = parse(&[]).unwrap();
+        assert!(matches!(command_line.command, Command::Run));
+        assert!(command_line.config_file.is_none());
+        assert!(command_line.layer.width.is_none());
+    }
+
+    #[test]
+    fn parses_options_with_separate_and_inline_values() {
+        let command_line = parse(&[
+            "--width",
+            "1280",
+            "--height=720",
+            "--vsync=off",
+            "--gpu",
+            "1",
+            "--config=other.toml",
+            "--log=vkrs=debug",
+            "--frame-sync=timeline",
+        ])
+        .unwrap();
+        assert_eq!(command_line.layer.width, Some(1280));
+        assert_eq!(command_line.layer.height, Some(720));
+        assert_eq!(
+            command_line.layer.present_mode,
+            Some(PresentModePreference::Off)
+        );
+        assert_eq!(
+            command_line.layer.device_selector,
+            Some(DeviceSelector::Index(1))
+        );
+        assert_eq!(command_line.config_file, Some(PathBuf::from("other.toml")));
+        assert_eq!(
+            command_line.layer.frame_sync,
+            Some(FrameSyncMode::TimelineSemaphore)
+        );
+        // Only the first `=` separates the value.
+        assert_eq!(command_line.layer.log_filter.as_deref(), Some("vkrs=debug"));
+    }
+
+    #[test]
+    fn parses_validation_options() {
+        let command_line = parse(&[
+            "--validation=best-practices",
+            "--validation-suppress",
+            "VUID-vkCmdDraw-None-02699,0x9981c31b",
+        ])
+        .unwrap();
+        assert!(command_line.layer.validation.unwrap().best_practices);
+        assert_eq!(
+            command_line.layer.suppressed_message_ids.unwrap(),
+            ["VUID-vkCmdDraw-None-02699", "0x9981c31b"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        assert_eq!(error(&["--width"]), "Missing value for --width");
+        assert_eq!(
+            error(&["--msaa=3"]),
+            "Invalid value 3 for --msaa, expected 1, 2, 4 or 8 samples"
+        );
+        assert_eq!(error(&["--vsnyc=off"]), "Unknown argument \"--vsnyc=off\"");
+        assert_eq!(
+            error(&["--json=true", "info"]),
+            "Unknown argument \"--json=true\""
+        );
+        assert_eq!(error(&["info", "bench"]), "Unknown argument \"bench\"");
+    }
+
+    #[test]
+    fn requests_help() {
+        assert!(matches!(parse(&["--width", "1280", "-h"]), Err(None)));
+        assert!(matches!(parse(&["--help"]), Err(None)));
+    }
+
+    #[test]
+    fn parses_info() {
+        let command_line = parse(&["info"]).unwrap();
+        assert!(matches!(
+            command_line.command,
+            Command::Info { json: false }
+        ));
+        let command_line = parse(&["--json", "info"]).unwrap();
+        assert!(matches!(command_line.command, Command::Info { json: true }));
+    }
+
+    #[test]
+    fn rejects_info_options_without_info() {
+        assert_eq!(
+            error(&["--json"]),
+            "--json is only supported by the info command"
+        );
+        assert_eq!(
+            error(&["bench", "--json"]),
+            "--json is only supported by the info command"
+        );
+    }
+
+    #[test]
+    fn parses_bench() {
+        let command_line = parse(&["bench", "--frames=500", "--warmup", "0"]).unwrap();
+        match command_line.command {
+            Command::Bench(config) => {
+                assert_eq!(config.frames, 500);
+                assert_eq!(config.warmup, 0);
+            }
+            _ => panic!("Expected the bench command"),
+        }
+
+        let defaults = BenchConfig::default();
+        match parse(&["bench"]).unwrap().command {
+            Command::Bench(config) => {
+                assert_eq!(config.frames, defaults.frames);
+                assert_eq!(config.warmup, defaults.warmup);
+            }
+            _ => panic!("Expected the bench command"),
+        }
+    }
+
+    #[test]
+    fn rejects_bench_options_without_bench() {
+        let message = "--frames and --warmup are only supported by the bench command";
+        assert_eq!(error(&["--frames", "10"]), message);
+        assert_eq!(error(&["info", "--warmup=10"]), message);
+        assert_eq!(
+            error(&["bench", "--frames=0"]),
+            "Invalid value 0 for --frames, expected a positive number"
+        );
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_lower_layers() {
+        let command_line = parse(&["--msaa=2", "--window", "maximized"]).unwrap();
+        let lower = Layer {
+            msaa_samples: Some(8),
+            width: Some(1024),
+            ..Layer::default()
+        };
+        let layer = command_line.layer.or(lower);
+        assert_eq!(layer.msaa_samples, Some(2));
+        assert_eq!(layer.window_mode, Some(WindowMode::Maximized));
+        assert_eq!(layer.width, Some(1024));
+    }
+}
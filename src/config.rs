@@ -0,0 +1,607 @@
+//! Settings from the command line, the environment and the `vkrs.toml` config file.
+//!
+//! Every source is read into a [`Layer`] of optional settings, and the layers are stacked with the
+//! command line over the environment over the file, falling back to the defaults for settings
+//! that none of them set. Invalid values are rejected with the source they came from.
+
+use vkrs::{
+    DeviceSelector, FrameSyncMode, HdrMode, MonitorSelector, PresentModePreference, RedrawMode,
+    ValidationConfig, VideoModeRequest, WindowMode,
+};
+
+use serde::Deserialize;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The config file that is read from the working directory, unless another one is given.
+pub const DEFAULT_CONFIG_FILE: &str = "vkrs.toml";
+
+/// Sample counts MSAA can be configured with, 1 disables it.
+const MSAA_SAMPLES: [u32; 4] = [1, 2, 4, 8];
+
+/// Settings from a single source, those that are not set fall through to the sources below it.
+#[derive(Default)]
+pub struct Layer {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub window_mode: Option<WindowMode>,
+    pub monitor: Option<MonitorSelector>,
+    pub video_mode: Option<VideoModeRequest>,
+    pub device_selector: Option<DeviceSelector>,
+    pub present_mode: Option<PresentModePreference>,
+    pub hdr: Option<HdrMode>,
+    pub swapchain_images: Option<u32>,
+    pub frames_in_flight: Option<u32>,
+    pub msaa_samples: Option<u32>,
+    pub redraw_mode: Option<RedrawMode>,
+    pub frame_sync: Option<FrameSyncMode>,
+    pub validation: Option<ValidationConfig>,
+    pub suppressed_message_ids: Option<Vec<String>>,
+    /// An `env_logger` filter such as `vkrs=info,vulkan=warn`.
+    pub log_filter: Option<String>,
+    pub scene: Option<PathBuf>,
+}
+
+/// The contents of a config file, where values that need parsing are kept as strings until the
+/// file has been read, so that they are rejected with the same messages as on the command line.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    window: WindowSection,
+    gpu: Option<String>,
+    vsync: Option<String>,
+    hdr: Option<String>,
+    swapchain_images: Option<u32>,
+    frames_in_flight: Option<u32>,
+    msaa: Option<u32>,
+    redraw: Option<String>,
+    frame_sync: Option<String>,
+    validation: Option<String>,
+    validation_suppress: Option<Vec<String>>,
+    log: Option<String>,
+    scene: Option<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct WindowSection {
+    width: Option<u32>,
+    height: Option<u32>,
+    mode: Option<String>,
+    monitor: Option<String>,
+    video_mode: Option<String>,
+}
+
+impl Layer {
+    /// Reads a config file, relative scene paths in it are relative to the file.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+        let file: ConfigFile = toml::from_str(&contents)
+            .map_err(|error| format!("Invalid config file {}: {}", path.display(), error))?;
+
+        let source = |key: &str| format!("{} in {}", key, path.display());
+        Ok(Self {
+            width: file
+                .window
+                .width
+                .map(|width| check_size(&source("window.width"), width))
+                .transpose()?,
+            height: file
+                .window
+                .height
+                .map(|height| check_size(&source("window.height"), height))
+                .transpose()?,
+            window_mode: file
+                .window
+                .mode
+                .map(|mode| parse_window_mode(&source("window.mode"), &mode))
+                .transpose()?,
+            monitor: file
+                .window
+                .monitor
+                .map(|monitor| MonitorSelector::parse(&monitor)),
+            video_mode: file
+                .window
+                .video_mode
+                .map(|video_mode| parse_video_mode(&source("window.video-mode"), &video_mode))
+                .transpose()?,
+            device_selector: file.gpu.map(|gpu| DeviceSelector::parse(&gpu)),
+            present_mode: file
+                .vsync
+                .map(|vsync| parse_present_mode(&source("vsync"), &vsync))
+                .transpose()?,
+            hdr: file
+                .hdr
+                .map(|hdr| parse_hdr_mode(&source("hdr"), &hdr))
+                .transpose()?,
+            swapchain_images: file
+                .swapchain_images
+                .map(|count| check_count(&source("swapchain-images"), count))
+                .transpose()?,
+            frames_in_flight: file
+                .frames_in_flight
+                .map(|count| check_count(&source("frames-in-flight"), count))
+                .transpose()?,
+            msaa_samples: file
+                .msaa
+                .map(|samples| check_msaa(&source("msaa"), samples))
+                .transpose()?,
+            redraw_mode: file
+                .redraw
+                .map(|redraw| parse_redraw_mode(&source("redraw"), &redraw))
+                .transpose()?,
+            frame_sync: file
+                .frame_sync
+                .map(|frame_sync| parse_frame_sync(&source("frame-sync"), &frame_sync))
+                .transpose()?,
+            validation: file
+                .validation
+                .map(|validation| parse_validation(&source("validation"), &validation))
+                .transpose()?,
+            suppressed_message_ids: file.validation_suppress,
+            log_filter: file.log,
+            scene: file.scene.map(|scene| match path.parent() {
+                Some(directory) => directory.join(scene),
+                None => scene,
+            }),
+        })
+    }
+
+    /// Reads the `VKRS_*` environment variables and `RUST_LOG`. Empty variables are ignored.
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            width: env("VKRS_WINDOW_WIDTH")
+                .map(|value| parse_size("VKRS_WINDOW_WIDTH", &value))
+                .transpose()?,
+            height: env("VKRS_WINDOW_HEIGHT")
+                .map(|value| parse_size("VKRS_WINDOW_HEIGHT", &value))
+                .transpose()?,
+            window_mode: env("VKRS_WINDOW_MODE")
+                .map(|value| parse_window_mode("VKRS_WINDOW_MODE", &value))
+                .transpose()?,
+            monitor: env("VKRS_MONITOR").map(|value| MonitorSelector::parse(&value)),
+            video_mode: env("VKRS_VIDEO_MODE")
+                .map(|value| parse_video_mode("VKRS_VIDEO_MODE", &value))
+                .transpose()?,
+            device_selector: env("VKRS_GPU").map(|value| DeviceSelector::parse(&value)),
+            present_mode: env("VKRS_PRESENT_MODE")
+                .map(|value| parse_present_mode("VKRS_PRESENT_MODE", &value))
+                .transpose()?,
+            hdr: env("VKRS_HDR")
+                .map(|value| parse_hdr_mode("VKRS_HDR", &value))
+                .transpose()?,
+            swapchain_images: env("VKRS_SWAPCHAIN_IMAGES")
+                .map(|value| parse_count("VKRS_SWAPCHAIN_IMAGES", &value))
+                .transpose()?,
+            frames_in_flight: env("VKRS_FRAMES_IN_FLIGHT")
+                .map(|value| parse_count("VKRS_FRAMES_IN_FLIGHT", &value))
+                .transpose()?,
+            msaa_samples: env("VKRS_MSAA")
+                .map(|value| parse_msaa("VKRS_MSAA", &value))
+                .transpose()?,
+            redraw_mode: env("VKRS_REDRAW")
+                .map(|value| parse_redraw_mode("VKRS_REDRAW", &value))
+                .transpose()?,
+            frame_sync: env("VKRS_FRAME_SYNC")
+                .map(|value| parse_frame_sync("VKRS_FRAME_SYNC", &value))
+                .transpose()?,
+            validation: env("VKRS_VALIDATION")
+                .map(|value| parse_validation("VKRS_VALIDATION", &value))
+                .transpose()?,
+            suppressed_message_ids: env("VKRS_VALIDATION_SUPPRESS")
+                .map(|value| ValidationConfig::parse_message_ids(&value)),
+            log_filter: env("RUST_LOG"),
+            scene: env("VKRS_SCENE").map(PathBuf::from),
+        })
+    }
+
+    /// Keeps the settings of `self`, and takes those it does not set from `lower`.
+    pub fn or(self, lower: Self) -> Self {
+        Self {
+            width: self.width.or(lower.width),
+            height: self.height.or(lower.height),
+            window_mode: self.window_mode.or(lower.window_mode),
+            monitor: self.monitor.or(lower.monitor),
+            video_mode: self.video_mode.or(lower.video_mode),
+            device_selector: self.device_selector.or(lower.device_selector),
+            present_mode: self.present_mode.or(lower.present_mode),
+            hdr: self.hdr.or(lower.hdr),
+            swapchain_images: self.swapchain_images.or(lower.swapchain_images),
+            frames_in_flight: self.frames_in_flight.or(lower.frames_in_flight),
+            msaa_samples: self.msaa_samples.or(lower.msaa_samples),
+            redraw_mode: self.redraw_mode.or(lower.redraw_mode),
+            frame_sync: self.frame_sync.or(lower.frame_sync),
+            validation: self.validation.or(lower.validation),
+            suppressed_message_ids: self.suppressed_message_ids.or(lower.suppressed_message_ids),
+            log_filter: self.log_filter.or(lower.log_filter),
+            scene: self.scene.or(lower.scene),
+        }
+    }
+}
+
+/// The config file to read: `explicit` if given, otherwise `VKRS_CONFIG` if set, otherwise
+/// [`DEFAULT_CONFIG_FILE`] if it exists.
+pub fn config_file_path(explicit: Option<PathBuf>) -> Option<PathBuf> {
+    explicit
+        .or_else(|| env("VKRS_CONFIG").map(PathBuf::from))
+        .or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+            default.exists().then_some(default)
+        })
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// The parsers below take the source of the value, such as `--vsync` or `VKRS_PRESENT_MODE`, for
+// their error messages.
+
+fn invalid(source: &str, value: impl std::fmt::Debug, expected: &str) -> String {
+    format!(
+        "Invalid value {:?} for {}, expected {}",
+        value, source, expected
+    )
+}
+
+fn one_of(names: impl IntoIterator<Item = &'static str>) -> String {
+    let names = names.into_iter().collect::<Vec<_>>();
+    format!("one of {}", names.join(", "))
+}
+
+pub fn parse_present_mode(source: &str, value: &str) -> Result<PresentModePreference, String> {
+    PresentModePreference::parse(value).ok_or_else(|| {
+        invalid(
+            source,
+            value,
+            &one_of(PresentModePreference::ALL.map(PresentModePreference::name)),
+        )
+    })
+}
+
+pub fn parse_hdr_mode(source: &str, value: &str) -> Result<HdrMode, String> {
+    HdrMode::parse(value)
+        .ok_or_else(|| invalid(source, value, &one_of(HdrMode::ALL.map(HdrMode::name))))
+}
+
+pub fn parse_window_mode(source: &str, value: &str) -> Result<WindowMode, String> {
+    WindowMode::parse(value).ok_or_else(|| {
+        invalid(
+            source,
+            value,
+            &one_of(WindowMode::ALL.map(WindowMode::name)),
+        )
+    })
+}
+
+pub fn parse_redraw_mode(source: &str, value: &str) -> Result<RedrawMode, String> {
+    RedrawMode::parse(value).ok_or_else(|| {
+        invalid(
+            source,
+            value,
+            &one_of(RedrawMode::ALL.map(RedrawMode::name)),
+        )
+    })
+}
+
+pub fn parse_frame_sync(source: &str, value: &str) -> Result<FrameSyncMode, String> {
+    FrameSyncMode::parse(value).ok_or_else(|| {
+        invalid(
+            source,
+            value,
+            &one_of(FrameSyncMode::ALL.map(FrameSyncMode::name)),
+        )
+    })
+}
+
+pub fn parse_video_mode(source: &str, value: &str) -> Result<VideoModeRequest, String> {
+    VideoModeRequest::parse(value).ok_or_else(|| {
+        invalid(
+            source,
+            value,
+            "a video mode such as 1920x1080 or 1920x1080@144",
+        )
+    })
+}
+
+pub fn parse_validation(source: &str, value: &str) -> Result<ValidationConfig, String> {
+    ValidationConfig::parse(value).map_err(|error| format!("Invalid {}: {}", source, error))
+}
+
+/// Parses a number of at least 1.
+pub fn parse_count(source: &str, value: &str) -> Result<u32, String> {
+    let count = value
+        .parse()
+        .map_err(|_| invalid(source, value, "a positive number"))?;
+    check_count(source, count)
+}
+
+fn check_count(source: &str, count: u32) -> Result<u32, String> {
+    if count == 0 {
+        return Err(invalid(source, count, "a positive number"));
+    }
+    Ok(count)
+}
+
+/// Parses a window width or height in logical pixels.
+pub fn parse_size(source: &str, value: &str) -> Result<u32, String> {
+    let size = value
+        .parse()
+        .map_err(|_| invalid(source, value, "a size in pixels"))?;
+    check_size(source, size)
+}
+
+fn check_size(source: &str, size: u32) -> Result<u32, String> {
+    if !(1..=16384).contains(&size) {
+        return Err(invalid(source, size, "a size from 1 to 16384 pixels"));
+    }
+    Ok(size)
+}
+
+pub fn parse_msaa(source: &str, value: &str) -> Result<u32, String> {
+    let samples = value
+        .parse()
+        .map_err(|_| invalid(source, value, "1, 2, 4 or 8 samples"))?;
+    check_msaa(source, samples)
+}
+
+fn check_msaa(source: &str, samples: u32) -> Result<u32, String> {
+    if !MSAA_SAMPLES.contains(&samples) {
+        return Err(invalid(source, samples, "1, 2, 4 or 8 samples"));
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempFile;
+
+    #[test]
+    fn reads_config_file() {
+        let config = TempFile::new(
+            "config-valid",
+            DEFAULT_CONFIG_FILE,
+            r#"
+                gpu = "nvidia"
+                vsync = "adaptive"
+                hdr = "hdr10"
+                swapchain-images = 3
+                frames-in-flight = 1
+                msaa = 4
+                redraw = "on-demand"
+                frame-sync = "timeline"
+                validation = "sync,fail-on-error"
+                validation-suppress = ["VUID-vkCmdDraw-None-02699"]
+                log = "vkrs=info"
+                scene = "scenes/triangle.json"
+
+                [window]
+                width = 1280
+                height = 720
+                mode = "exclusive"
+                monitor = "1"
+                video-mode = "1920x1080@144"
+            "#,
+        );
+        let layer = Layer::from_file(config.path()).unwrap();
+        assert_eq!(layer.width, Some(1280));
+        assert_eq!(layer.height, Some(720));
+        assert_eq!(layer.window_mode, Some(WindowMode::Exclusive));
+        assert_eq!(layer.monitor, Some(MonitorSelector::Index(1)));
+        assert_eq!(
+            layer.video_mode,
+            Some(VideoModeRequest {
+                width: 1920,
+                height: 1080,
+                refresh_rate: Some(144),
+            })
+        );
+        assert_eq!(
+            layer.device_selector,
+            Some(DeviceSelector::Name("nvidia".to_owned()))
+        );
+        assert_eq!(layer.present_mode, Some(PresentModePreference::Adaptive));
+        assert_eq!(layer.hdr, Some(HdrMode::Hdr10));
+        assert_eq!(layer.swapchain_images, Some(3));
+        assert_eq!(layer.frames_in_flight, Some(1));
+        assert_eq!(layer.msaa_samples, Some(4));
+        assert_eq!(layer.redraw_mode, Some(RedrawMode::OnDemand));
+        assert_eq!(layer.frame_sync, Some(FrameSyncMode::TimelineSemaphore));
+        let validation = layer.validation.unwrap();
+        assert!(validation.synchronization && validation.fail_on_error);
+        assert_eq!(
+            layer.suppressed_message_ids.unwrap(),
+            ["VUID-vkCmdDraw-None-02699"]
+        );
+        assert_eq!(layer.log_filter.as_deref(), Some("vkrs=info"));
+        // Relative to the directory of the config file rather than the working directory.
+        assert_eq!(
+            layer.scene,
+            Some(config.path().parent().unwrap().join("scenes/triangle.json"))
+        );
+    }
+
+    #[test]
+    fn reads_empty_config_file() {
+        let config = TempFile::new("config-empty", DEFAULT_CONFIG_FILE, "");
+        let layer = Layer::from_file(config.path()).unwrap();
+        assert!(layer.width.is_none());
+        assert!(layer.present_mode.is_none());
+        assert!(layer.scene.is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let config = TempFile::new(
+            "config-unknown-key",
+            DEFAULT_CONFIG_FILE,
+            "vsinc = \"off\"\n",
+        );
+        let error = Layer::from_file(config.path()).err().unwrap();
+        assert!(error.contains("vsinc"), "{}", error);
+
+        let config = TempFile::new(
+            "config-unknown-window-key",
+            DEFAULT_CONFIG_FILE,
+            "[window]\nwdith = 1280\n",
+        );
+        let error = Layer::from_file(config.path()).err().unwrap();
+        assert!(error.contains("wdith"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_values_with_their_key() {
+        let config = TempFile::new("config-invalid-value", DEFAULT_CONFIG_FILE, "msaa = 3\n");
+        let error = Layer::from_file(config.path()).err().unwrap();
+        assert!(
+            error.starts_with("Invalid value 3 for msaa in "),
+            "{}",
+            error
+        );
+
+        let config = TempFile::new(
+            "config-invalid-window-value",
+            DEFAULT_CONFIG_FILE,
+            "[window]\nwidth = 0\n",
+        );
+        let error = Layer::from_file(config.path()).err().unwrap();
+        assert!(
+            error.starts_with("Invalid value 0 for window.width in "),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_missing_config_file() {
+        let path = std::env::temp_dir().join("vkrs-config-test-missing.toml");
+        assert!(Layer::from_file(&path).is_err());
+    }
+
+    #[test]
+    fn upper_layers_take_precedence() {
+        let command_line = Layer {
+            present_mode: Some(PresentModePreference::Off),
+            ..Layer::default()
+        };
+        let env = Layer {
+            present_mode: Some(PresentModePreference::On),
+            width: Some(1024),
+            ..Layer::default()
+        };
+        let file = Layer {
+            present_mode: Some(PresentModePreference::Adaptive),
+            width: Some(1280),
+            msaa_samples: Some(4),
+            ..Layer::default()
+        };
+        let layer = command_line.or(env).or(file);
+        assert_eq!(layer.present_mode, Some(PresentModePreference::Off));
+        assert_eq!(layer.width, Some(1024));
+        assert_eq!(layer.msaa_samples, Some(4));
+        // Left to the defaults.
+        assert_eq!(layer.height, None);
+    }
+
+    #[test]
+    fn parses_counts() {
+        assert_eq!(parse_count("--frames-in-flight", "3"), Ok(3));
+        assert_eq!(
+            parse_count("--frames-in-flight", "0"),
+            Err("Invalid value 0 for --frames-in-flight, expected a positive number".to_owned())
+        );
+        assert_eq!(
+            parse_count("VKRS_FRAMES_IN_FLIGHT", "two"),
+            Err(
+                "Invalid value \"two\" for VKRS_FRAMES_IN_FLIGHT, expected a positive number"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("--width", "1"), Ok(1));
+        assert_eq!(parse_size("--width", "16384"), Ok(16384));
+        assert!(parse_size("--width", "0").is_err());
+        assert!(parse_size("--width", "16385").is_err());
+        assert!(parse_size("--width", "-1").is_err());
+    }
+
+    #[test]
+    fn parses_msaa() {
+        for samples in MSAA_SAMPLES {
+            assert_eq!(parse_msaa("--msaa", &samples.to_string()), Ok(samples));
+        }
+        assert!(parse_msaa("--msaa", "0").is_err());
+        assert!(parse_msaa("--msaa", "16").is_err());
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!(
+            parse_present_mode("--vsync", "low-latency"),
+            Ok(PresentModePreference::LowLatency)
+        );
+        assert_eq!(
+            parse_present_mode("--vsync", "mailbox"),
+            Err(
+                "Invalid value \"mailbox\" for --vsync, expected one of on, off, adaptive, \
+                 low-latency"
+                    .to_owned()
+            )
+        );
+        assert_eq!(parse_hdr_mode("--hdr", "scrgb"), Ok(HdrMode::ScRgb));
+        assert!(parse_hdr_mode("--hdr", "hdr").is_err());
+        assert_eq!(
+            parse_window_mode("--window", "borderless"),
+            Ok(WindowMode::Borderless)
+        );
+        assert!(parse_window_mode("--window", "fullscreen").is_err());
+        assert_eq!(
+            parse_redraw_mode("--redraw", "continuous"),
+            Ok(RedrawMode::Continuous)
+        );
+        assert!(parse_redraw_mode("--redraw", "lazy").is_err());
+        assert_eq!(
+            parse_frame_sync("--frame-sync", "fences"),
+            Ok(FrameSyncMode::Fences)
+        );
+        assert_eq!(
+            parse_frame_sync("VKRS_FRAME_SYNC", "semaphores"),
+            Err(
+                "Invalid value \"semaphores\" for VKRS_FRAME_SYNC, expected one of fences, \
+                 timeline"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            parse_video_mode("--video-mode", "2560x1440"),
+            Ok(VideoModeRequest {
+                width: 2560,
+                height: 1440,
+                refresh_rate: None,
+            })
+        );
+        assert!(parse_video_mode("--video-mode", "2560").is_err());
+        assert!(parse_video_mode("--video-mode", "2560x1440@").is_err());
+    }
+
+    #[test]
+    fn parses_validation_with_its_source() {
+        assert!(
+            parse_validation("--validation", "gpu-assisted")
+                .unwrap()
+                .gpu_assisted
+        );
+        assert_eq!(
+            parse_validation("VKRS_VALIDATION", "gpu").err().unwrap(),
+            "Invalid VKRS_VALIDATION: Unknown validation feature \"gpu\""
+        );
+    }
+}
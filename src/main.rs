@@ -1,21 +1,84 @@
-mod vkrs;
+mod cli;
+mod config;
+#[cfg(test)]
+mod test_util;
 
 use winit::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder};
 
 const NAME: &str = "vkrs";
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
 
 fn main() {
-    env_logger::init();
+    let args = cli::Args::parse();
+
+    let mut logger = env_logger::Builder::new();
+    if let Some(filter) = &args.log_filter {
+        logger.parse_filters(filter);
+    }
+    if let Ok(style) = std::env::var("RUST_LOG_STYLE") {
+        logger.parse_write_style(&style);
+    }
+    logger.init();
+    if let Some(path) = &args.config_file {
+        log::info!(target: "vkrs", "Read config file {}", path.display());
+    }
+
+    let scene = match &args.scene {
+        Some(path) => vkrs::Scene::load(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }),
+        None => vkrs::Scene::quad(),
+    };
 
     let event_loop = EventLoop::new();
+    let fullscreen = vkrs::choose_fullscreen(
+        &event_loop,
+        args.window.mode,
+        args.window.monitor.as_ref(),
+        args.window.video_mode.as_ref(),
+    );
     let window = WindowBuilder::new()
         .with_title(NAME)
-        .with_inner_size(LogicalSize::new(f64::from(WIDTH), f64::from(HEIGHT)))
+        .with_inner_size(LogicalSize::new(
+            f64::from(args.window.width),
+            f64::from(args.window.height),
+        ))
+        .with_maximized(args.window.mode == vkrs::WindowMode::Maximized)
+        .with_fullscreen(args.window.mode.is_fullscreen().then(|| fullscreen.clone()))
+        // The info command only needs the window for its surface.
+        .with_visible(!matches!(args.command, cli::Command::Info { .. }))
         .build(&event_loop)
         .expect("Failed to create window.");
 
-    let app = vkrs::App::new(NAME, &window);
-    app.run(event_loop, window);
+    let app_config = vkrs::AppConfig {
+        renderer: vkrs::RendererConfig {
+            device_selector: args.device_selector,
+            swapchain_preferences: args.swapchain_preferences,
+            frames_in_flight: args.frames_in_flight,
+            msaa_samples: args.msaa_samples,
+            frame_sync: args.frame_sync,
+            validation: args.validation,
+        },
+        redraw_mode: args.redraw_mode,
+        scene,
+        fullscreen,
+    };
+    match args.command {
+        cli::Command::Run => {
+            let app = vkrs::App::new(NAME, &window, &app_config);
+            app.run(event_loop, window);
+        }
+        cli::Command::Bench(config) => {
+            let app = vkrs::App::new(NAME, &window, &app_config);
+            app.bench(event_loop, window, config);
+        }
+        cli::Command::Info { json } => vkrs::print_device_info(
+            NAME,
+            &window,
+            app_config.renderer.device_selector.as_ref(),
+            &app_config.renderer.validation,
+            app_config.renderer.frame_sync,
+            json,
+        ),
+    }
 }
@@ -0,0 +1,37 @@
+//! Helpers shared by the tests of the library and the binary.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A file in a directory of its own in the temporary directory, which is removed when the
+/// `TempFile` is dropped.
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Writes `contents` to `file_name` in a directory named after the test `name`, which must be
+    /// unique among the tests of the crate since they run in parallel.
+    pub fn new(name: &str, file_name: &str, contents: &str) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("vkrs-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(file_name);
+        fs::write(&path, contents).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Some(directory) = self.path.parent() {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
@@ -1,11 +0,0 @@
-mod app;
-mod extensions;
-mod queue_family_indices;
-mod shader;
-mod swapchain;
-mod uniform_buffer_object;
-mod validation;
-mod vertex;
-mod vulkan;
-
-pub use app::App;
@@ -0,0 +1,259 @@
+//! Benchmark runs, which render a fixed number of frames with a fixed timestep and vsync off, and
+//! report how long they took as JSON.
+//!
+//! Reports are keyed by device and driver, since only runs on the same machine can be compared.
+
+use super::gpu_profiler::GpuTiming;
+use super::handles::MemoryUsage;
+
+use ash::vk;
+use serde::Serialize;
+
+use std::{
+    collections::BTreeMap,
+    ffi::CStr,
+    time::{Duration, Instant},
+};
+
+/// The time the scene advances by every frame, so that every run renders the same frames.
+pub const BENCH_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub const DEFAULT_BENCH_FRAMES: u32 = 1000;
+pub const DEFAULT_BENCH_WARMUP: u32 = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BenchConfig {
+    /// Number of frames that are measured.
+    pub frames: u32,
+    /// Number of frames rendered before measuring, while caches and clocks settle.
+    pub warmup: u32,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            frames: DEFAULT_BENCH_FRAMES,
+            warmup: DEFAULT_BENCH_WARMUP,
+        }
+    }
+}
+
+/// The device and driver a report was made on.
+#[derive(Serialize)]
+struct DeviceIdentity {
+    name: String,
+    vendor_id: u32,
+    device_id: u32,
+    api_version: String,
+    driver_id: String,
+    driver_name: String,
+    driver_info: String,
+    /// Encoded in a vendor specific way, `driver_info` usually has a readable version.
+    driver_version: u32,
+}
+
+impl DeviceIdentity {
+    fn new(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> Self {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        // Part of Vulkan 1.2, older devices report no driver.
+        let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+        if properties.api_version >= vk::API_VERSION_1_2 {
+            let mut properties2 =
+                vk::PhysicalDeviceProperties2::builder().push_next(&mut driver_properties);
+            unsafe { instance.get_physical_device_properties2(physical_device, &mut properties2) };
+        }
+        let string = |chars: &[std::os::raw::c_char]| {
+            unsafe { CStr::from_ptr(chars.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        Self {
+            name: string(&properties.device_name),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: format!(
+                "{}.{}.{}",
+                vk::api_version_major(properties.api_version),
+                vk::api_version_minor(properties.api_version),
+                vk::api_version_patch(properties.api_version)
+            ),
+            driver_id: format!("{:?}", driver_properties.driver_id),
+            driver_name: string(&driver_properties.driver_name),
+            driver_info: string(&driver_properties.driver_info),
+            driver_version: properties.driver_version,
+        }
+    }
+
+    fn key(&self) -> String {
+        format!(
+            "{} ({:04x}:{:04x}) / {} {}",
+            self.name, self.vendor_id, self.device_id, self.driver_name, self.driver_info
+        )
+    }
+}
+
+/// Summary of a set of samples, in milliseconds.
+#[derive(Serialize)]
+struct Distribution {
+    samples: usize,
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p95: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Distribution {
+    fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len().max(1) as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = sorted
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        let percentile = |p: f64| {
+            sorted
+                .get(((sorted.len().max(1) - 1) as f64 * p).round() as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+        Self {
+            samples: sorted.len(),
+            mean,
+            std_dev: variance.sqrt(),
+            min: percentile(0.0),
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: percentile(1.0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct BenchReport {
+    /// Device and driver, only reports with the same key are comparable.
+    key: String,
+    vkrs_version: &'static str,
+    device: DeviceIdentity,
+    scene: String,
+    frames: u32,
+    warmup: u32,
+    timestep_ms: f64,
+    extent: [u32; 2],
+    frames_in_flight: usize,
+    swapchain_images: usize,
+    fps: f64,
+    frame_time_ms: Distribution,
+    /// Keyed by the scope and its parents, such as `/frame/scene pass`. Empty if the graphics
+    /// queue does not support timestamps.
+    gpu_time_ms: BTreeMap<String, Distribution>,
+    memory: MemoryUsage,
+}
+
+/// What the report says about the renderer, besides the measurements.
+pub struct BenchSettings<'a> {
+    pub instance: &'a ash::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub scene: &'a str,
+    pub extent: vk::Extent2D,
+    pub frames_in_flight: usize,
+    pub swapchain_images: usize,
+    pub memory: MemoryUsage,
+}
+
+/// Collects the frame times and GPU timings of a benchmark run.
+pub struct Benchmark {
+    config: BenchConfig,
+    frames_drawn: u32,
+    last_frame_end: Option<Instant>,
+    frame_times: Vec<f64>,
+    gpu_times: BTreeMap<String, Vec<f64>>,
+    last_gpu_frame: u64,
+}
+
+impl Benchmark {
+    pub fn new(config: BenchConfig) -> Self {
+        log::info!(target: "vkrs",
+                   "Benchmarking {} frames after {} warmup frames",
+                   config.frames,
+                   config.warmup);
+        Self {
+            config,
+            frames_drawn: 0,
+            last_frame_end: None,
+            frame_times: Vec::with_capacity(config.frames as usize),
+            gpu_times: BTreeMap::new(),
+            last_gpu_frame: 0,
+        }
+    }
+
+    /// Called after every frame. `gpu_frame` counts the frames the GPU timings have been
+    /// collected for, so that the same timings are not recorded twice.
+    pub fn record_frame(&mut self, gpu_frame: u64, gpu_timings: &[GpuTiming]) {
+        let now = Instant::now();
+        let last_frame_end = self.last_frame_end.replace(now);
+        self.frames_drawn += 1;
+        let new_gpu_timings = gpu_frame != self.last_gpu_frame;
+        self.last_gpu_frame = gpu_frame;
+        if self.frames_drawn <= self.config.warmup {
+            if self.frames_drawn == self.config.warmup {
+                log::info!(target: "vkrs", "Warmup finished");
+            }
+            return;
+        }
+
+        // Without warmup the first frame has nothing to be measured from.
+        if let Some(last_frame_end) = last_frame_end {
+            self.frame_times
+                .push((now - last_frame_end).as_secs_f64() * 1000.0);
+        }
+        if new_gpu_timings {
+            for (path, milliseconds) in GpuTiming::flatten(gpu_timings) {
+                self.gpu_times.entry(path).or_default().push(milliseconds);
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames_drawn >= self.config.warmup + self.config.frames
+    }
+
+    /// The report as pretty-printed JSON.
+    pub fn report(&self, settings: &BenchSettings) -> String {
+        let device = DeviceIdentity::new(settings.instance, settings.physical_device);
+        let total_ms = self.frame_times.iter().sum::<f64>();
+        let report = BenchReport {
+            key: device.key(),
+            vkrs_version: env!("CARGO_PKG_VERSION"),
+            device,
+            scene: settings.scene.to_owned(),
+            frames: self.config.frames,
+            warmup: self.config.warmup,
+            timestep_ms: BENCH_TIMESTEP.as_secs_f64() * 1000.0,
+            extent: [settings.extent.width, settings.extent.height],
+            frames_in_flight: settings.frames_in_flight,
+            swapchain_images: settings.swapchain_images,
+            fps: if total_ms > 0.0 {
+                self.frame_times.len() as f64 * 1000.0 / total_ms
+            } else {
+                0.0
+            },
+            frame_time_ms: Distribution::new(&self.frame_times),
+            gpu_time_ms: self
+                .gpu_times
+                .iter()
+                .map(|(path, samples)| (path.clone(), Distribution::new(samples)))
+                .collect(),
+            memory: settings.memory,
+        };
+        serde_json::to_string_pretty(&report).expect("Failed to serialize benchmark report.")
+    }
+}
@@ -0,0 +1,44 @@
+//! Where the scene is viewed from.
+
+use glam::{const_vec3, Mat4, Vec3};
+
+/// A perspective camera looking from `position` at `target`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+    /// Distances of the near and far clip planes, both positive.
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Camera {
+    /// Looks down at the origin from above, with +z up.
+    fn default() -> Self {
+        Self {
+            position: const_vec3!([2.0, 2.0, 2.0]),
+            target: Vec3::ZERO,
+            up: Vec3::Z,
+            fov_y: f32::to_radians(45.0),
+            near: 0.1,
+            far: 10.0,
+        }
+    }
+}
+
+impl Camera {
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position, self.target, self.up)
+    }
+
+    /// The projection for a target of `aspect_ratio`, flipped for the Vulkan clip space where y
+    /// points down.
+    pub fn projection(&self, aspect_ratio: f32) -> Mat4 {
+        let mut proj = Mat4::perspective_rh(self.fov_y, aspect_ratio, self.near, self.far);
+        proj.y_axis.y *= -1.0;
+        proj
+    }
+}
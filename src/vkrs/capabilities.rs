@@ -0,0 +1,11 @@
+use super::device_features::DeviceFeatures;
+use super::extensions::EnabledExtensions;
+
+/// The extensions and features that were actually enabled on a device, so that code paths can
+/// branch on optional ones.
+pub struct Capabilities {
+    /// The Vulkan version of the device, which decides the feature structs that can be chained.
+    pub api_version: u32,
+    pub extensions: EnabledExtensions,
+    pub features: DeviceFeatures,
+}
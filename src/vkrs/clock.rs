@@ -0,0 +1,36 @@
+//! The time the scene is animated at.
+
+use std::time::{Duration, Instant};
+
+pub enum Clock {
+    /// Wall-clock time since the clock was created.
+    RealTime(Instant),
+    /// Advances by `step` every frame regardless of how long frames take, so that every run
+    /// renders the same frames.
+    Fixed { step: Duration, frames: u32 },
+}
+
+impl Clock {
+    pub fn real_time() -> Self {
+        Self::RealTime(Instant::now())
+    }
+
+    pub fn fixed(step: Duration) -> Self {
+        Self::Fixed { step, frames: 0 }
+    }
+
+    /// Seconds since the start of the animation.
+    pub fn time(&self) -> f32 {
+        match self {
+            Self::RealTime(start) => start.elapsed().as_secs_f32(),
+            Self::Fixed { step, frames } => (*step * *frames).as_secs_f32(),
+        }
+    }
+
+    /// Called once a frame has been drawn.
+    pub fn advance(&mut self) {
+        if let Self::Fixed { frames, .. } = self {
+            *frames += 1;
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use std::any::Any;
+
+/// Holds on to resources until the GPU progress value of the last submission that used them has
+/// been reached, so that they can be released without waiting for the device to become idle.
+///
+/// Resources are the owning wrappers from `handles`, which are destroyed by dropping them.
+pub struct DeletionQueue {
+    entries: Vec<(u64, Box<dyn Any>)>,
+}
+
+impl DeletionQueue {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Queues `resource` for destruction once `timeline_value` has completed on the GPU.
+    pub fn push<T: 'static>(&mut self, timeline_value: u64, resource: T) {
+        self.entries.push((timeline_value, Box::new(resource)));
+    }
+
+    /// Destroys every queued resource whose GPU progress value has completed.
+    pub fn collect(&mut self, completed_value: u64) {
+        self.entries.retain(|(value, _)| *value > completed_value);
+    }
+}
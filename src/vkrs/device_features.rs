@@ -0,0 +1,448 @@
+use super::extensions::{self, ExtensionRequests};
+
+use ash::vk;
+
+use std::{ffi::CStr, os::raw::c_void, ptr};
+
+/// A Vulkan feature struct, accessed as a list of named `VkBool32` members.
+pub trait FeatureStruct {
+    fn fields(&self) -> Vec<(&'static str, vk::Bool32)>;
+    fn fields_mut(&mut self) -> Vec<&mut vk::Bool32>;
+}
+
+/// A feature struct that is provided by a device extension, and chained through its `pNext`.
+pub trait ExtensionFeatureStruct: FeatureStruct {
+    /// Links `next` after this struct and returns a pointer to this struct for the chain.
+    fn chain(&mut self, next: *mut c_void) -> *mut c_void;
+    /// Returns a struct of the same type with every feature disabled.
+    fn empty(&self) -> Box<dyn ExtensionFeatureStruct>;
+}
+
+macro_rules! feature_struct {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl FeatureStruct for $ty {
+            fn fields(&self) -> Vec<(&'static str, vk::Bool32)> {
+                vec![$((stringify!($field), self.$field)),*]
+            }
+
+            fn fields_mut(&mut self) -> Vec<&mut vk::Bool32> {
+                vec![$(&mut self.$field),*]
+            }
+        }
+    };
+}
+
+macro_rules! extension_feature_struct {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        feature_struct!($ty { $($field),* });
+
+        impl ExtensionFeatureStruct for $ty {
+            fn chain(&mut self, next: *mut c_void) -> *mut c_void {
+                self.p_next = next;
+                self as *mut Self as *mut c_void
+            }
+
+            fn empty(&self) -> Box<dyn ExtensionFeatureStruct> {
+                Box::new(<$ty>::default())
+            }
+        }
+    };
+}
+
+feature_struct!(vk::PhysicalDeviceFeatures {
+    robust_buffer_access,
+    full_draw_index_uint32,
+    image_cube_array,
+    independent_blend,
+    geometry_shader,
+    tessellation_shader,
+    sample_rate_shading,
+    dual_src_blend,
+    logic_op,
+    multi_draw_indirect,
+    draw_indirect_first_instance,
+    depth_clamp,
+    depth_bias_clamp,
+    fill_mode_non_solid,
+    depth_bounds,
+    wide_lines,
+    large_points,
+    alpha_to_one,
+    multi_viewport,
+    sampler_anisotropy,
+    texture_compression_etc2,
+    texture_compression_astc_ldr,
+    texture_compression_bc,
+    occlusion_query_precise,
+    pipeline_statistics_query,
+    vertex_pipeline_stores_and_atomics,
+    fragment_stores_and_atomics,
+    shader_tessellation_and_geometry_point_size,
+    shader_image_gather_extended,
+    shader_storage_image_extended_formats,
+    shader_storage_image_multisample,
+    shader_storage_image_read_without_format,
+    shader_storage_image_write_without_format,
+    shader_uniform_buffer_array_dynamic_indexing,
+    shader_sampled_image_array_dynamic_indexing,
+    shader_storage_buffer_array_dynamic_indexing,
+    shader_storage_image_array_dynamic_indexing,
+    shader_clip_distance,
+    shader_cull_distance,
+    shader_float64,
+    shader_int64,
+    shader_int16,
+    shader_resource_residency,
+    shader_resource_min_lod,
+    sparse_binding,
+    sparse_residency_buffer,
+    sparse_residency_image2_d,
+    sparse_residency_image3_d,
+    sparse_residency2_samples,
+    sparse_residency4_samples,
+    sparse_residency8_samples,
+    sparse_residency16_samples,
+    sparse_residency_aliased,
+    variable_multisample_rate,
+    inherited_queries,
+});
+
+feature_struct!(vk::PhysicalDeviceVulkan11Features {
+    storage_buffer16_bit_access,
+    uniform_and_storage_buffer16_bit_access,
+    storage_push_constant16,
+    storage_input_output16,
+    multiview,
+    multiview_geometry_shader,
+    multiview_tessellation_shader,
+    variable_pointers_storage_buffer,
+    variable_pointers,
+    protected_memory,
+    sampler_ycbcr_conversion,
+    shader_draw_parameters,
+});
+
+feature_struct!(vk::PhysicalDeviceVulkan12Features {
+    sampler_mirror_clamp_to_edge,
+    draw_indirect_count,
+    storage_buffer8_bit_access,
+    uniform_and_storage_buffer8_bit_access,
+    storage_push_constant8,
+    shader_buffer_int64_atomics,
+    shader_shared_int64_atomics,
+    shader_float16,
+    shader_int8,
+    descriptor_indexing,
+    shader_input_attachment_array_dynamic_indexing,
+    shader_uniform_texel_buffer_array_dynamic_indexing,
+    shader_storage_texel_buffer_array_dynamic_indexing,
+    shader_uniform_buffer_array_non_uniform_indexing,
+    shader_sampled_image_array_non_uniform_indexing,
+    shader_storage_buffer_array_non_uniform_indexing,
+    shader_storage_image_array_non_uniform_indexing,
+    shader_input_attachment_array_non_uniform_indexing,
+    shader_uniform_texel_buffer_array_non_uniform_indexing,
+    shader_storage_texel_buffer_array_non_uniform_indexing,
+    descriptor_binding_uniform_buffer_update_after_bind,
+    descriptor_binding_sampled_image_update_after_bind,
+    descriptor_binding_storage_image_update_after_bind,
+    descriptor_binding_storage_buffer_update_after_bind,
+    descriptor_binding_uniform_texel_buffer_update_after_bind,
+    descriptor_binding_storage_texel_buffer_update_after_bind,
+    descriptor_binding_update_unused_while_pending,
+    descriptor_binding_partially_bound,
+    descriptor_binding_variable_descriptor_count,
+    runtime_descriptor_array,
+    sampler_filter_minmax,
+    scalar_block_layout,
+    imageless_framebuffer,
+    uniform_buffer_standard_layout,
+    shader_subgroup_extended_types,
+    separate_depth_stencil_layouts,
+    host_query_reset,
+    timeline_semaphore,
+    buffer_device_address,
+    buffer_device_address_capture_replay,
+    buffer_device_address_multi_device,
+    vulkan_memory_model,
+    vulkan_memory_model_device_scope,
+    vulkan_memory_model_availability_visibility_chains,
+    shader_output_viewport_index,
+    shader_output_layer,
+    subgroup_broadcast_dynamic_id,
+});
+
+extension_feature_struct!(vk::PhysicalDeviceExtendedDynamicStateFeaturesEXT {
+    extended_dynamic_state
+});
+
+extension_feature_struct!(vk::PhysicalDeviceRobustness2FeaturesEXT {
+    robust_buffer_access2,
+    robust_image_access2,
+    null_descriptor,
+});
+
+/// Features of a device extension, the extension is enabled along with them.
+pub struct ExtensionFeatures {
+    pub extension: &'static CStr,
+    pub features: Box<dyn ExtensionFeatureStruct>,
+}
+
+/// A set of core and extension features, used both for what a device supports and for what is
+/// requested from or enabled on it.
+#[derive(Default)]
+pub struct DeviceFeatures {
+    pub core: vk::PhysicalDeviceFeatures,
+    pub vulkan_11: vk::PhysicalDeviceVulkan11Features,
+    pub vulkan_12: vk::PhysicalDeviceVulkan12Features,
+    pub extensions: Vec<ExtensionFeatures>,
+}
+
+/// Features and extensions that subsystems need from the device. Devices missing a required
+/// feature or extension are rejected, optional features are enabled when supported and make a
+/// device score higher, as do optional extensions.
+pub struct DeviceRequirements {
+    pub required: DeviceFeatures,
+    pub optional: DeviceFeatures,
+    pub extensions: ExtensionRequests,
+}
+
+impl DeviceRequirements {
+    /// Starts out with the extensions every device needs, see
+    /// [`extensions::get_device_extension_requests`].
+    pub fn new(present: bool) -> Self {
+        Self {
+            required: DeviceFeatures::default(),
+            optional: DeviceFeatures::default(),
+            extensions: extensions::get_device_extension_requests(present),
+        }
+    }
+
+    /// Returns the extension requests including the extensions of requested extension features.
+    pub fn extension_requests(&self) -> ExtensionRequests {
+        let mut requests = self.extensions.clone();
+        for extension in self.required.extension_names() {
+            requests.require(extension);
+        }
+        for extension in self.optional.extension_names() {
+            requests.request(extension);
+        }
+        requests
+    }
+}
+
+impl DeviceFeatures {
+    /// Adds features of `extension` to the set, merging them into any already added for it.
+    pub fn add_extension_features(
+        &mut self,
+        extension: &'static CStr,
+        features: Box<dyn ExtensionFeatureStruct>,
+    ) {
+        match self.extension_features_mut(extension) {
+            Some(existing) => merge(existing, &*features),
+            None => self.extensions.push(ExtensionFeatures {
+                extension,
+                features,
+            }),
+        }
+    }
+
+    fn extension_features(&self, extension: &CStr) -> Option<&dyn ExtensionFeatureStruct> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.extension == extension)
+            .map(|ext| &*ext.features)
+    }
+
+    fn extension_features_mut(
+        &mut self,
+        extension: &CStr,
+    ) -> Option<&mut dyn ExtensionFeatureStruct> {
+        self.extensions
+            .iter_mut()
+            .find(|ext| ext.extension == extension)
+            .map(|ext| &mut *ext.features as &mut dyn ExtensionFeatureStruct)
+    }
+
+    pub fn extension_names(&self) -> Vec<&'static CStr> {
+        self.extensions.iter().map(|ext| ext.extension).collect()
+    }
+
+    /// Queries which of the features in `template` are supported by `physical_device`, which
+    /// supports Vulkan `api_version`. Extension features are only queried for extensions in
+    /// `available_extensions`.
+    pub fn query(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        api_version: u32,
+        template: &DeviceFeatures,
+        available_extensions: &[&CStr],
+    ) -> Self {
+        let mut supported = Self {
+            extensions: template
+                .extensions
+                .iter()
+                .filter(|ext| available_extensions.contains(&ext.extension))
+                .map(|ext| ExtensionFeatures {
+                    extension: ext.extension,
+                    features: ext.features.empty(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+        supported.with_chain(api_version, |features| unsafe {
+            instance.get_physical_device_features2(physical_device, features)
+        });
+        supported
+    }
+
+    /// Calls `f` with a `VkPhysicalDeviceFeatures2` chain that points into this set, for
+    /// querying features or enabling them on device creation. The Vulkan 1.1 and 1.2 feature
+    /// structs are only chained for devices that support Vulkan 1.2 (`api_version`), on older
+    /// devices they are left out and their features count as unsupported.
+    pub fn with_chain<R>(
+        &mut self,
+        api_version: u32,
+        f: impl FnOnce(&mut vk::PhysicalDeviceFeatures2) -> R,
+    ) -> R {
+        let mut next: *mut c_void = ptr::null_mut();
+        for ext in self.extensions.iter_mut().rev() {
+            next = ext.features.chain(next);
+        }
+        if api_version >= vk::API_VERSION_1_2 {
+            self.vulkan_12.p_next = next;
+            self.vulkan_11.p_next = &mut self.vulkan_12 as *mut _ as *mut c_void;
+            next = &mut self.vulkan_11 as *mut _ as *mut c_void;
+        } else {
+            self.vulkan_11 = Default::default();
+            self.vulkan_12 = Default::default();
+        }
+        let mut features = vk::PhysicalDeviceFeatures2 {
+            p_next: next,
+            features: self.core,
+            ..Default::default()
+        };
+        let result = f(&mut features);
+        self.core = features.features;
+        result
+    }
+
+    /// Returns the names of the features in this set that are not in `supported`.
+    pub fn missing(&self, supported: &DeviceFeatures) -> Vec<String> {
+        let mut missing = Vec::new();
+        compare("core", &self.core, &supported.core, &mut missing);
+        compare(
+            "vulkan_11",
+            &self.vulkan_11,
+            &supported.vulkan_11,
+            &mut missing,
+        );
+        compare(
+            "vulkan_12",
+            &self.vulkan_12,
+            &supported.vulkan_12,
+            &mut missing,
+        );
+        for ext in &self.extensions {
+            let extension = ext.extension.to_string_lossy();
+            match supported.extension_features(ext.extension) {
+                Some(have) => compare(&extension, &*ext.features, have, &mut missing),
+                None => missing.push(format!("{} extension", extension)),
+            }
+        }
+        missing
+    }
+
+    /// Returns the features of this set that are also in `supported`.
+    pub fn intersection(&self, supported: &DeviceFeatures) -> Self {
+        let mut result = Self {
+            core: self.core,
+            vulkan_11: self.vulkan_11,
+            vulkan_12: self.vulkan_12,
+            extensions: Vec::new(),
+        };
+        intersect(&mut result.core, &supported.core);
+        intersect(&mut result.vulkan_11, &supported.vulkan_11);
+        intersect(&mut result.vulkan_12, &supported.vulkan_12);
+        for ext in &self.extensions {
+            if let Some(have) = supported.extension_features(ext.extension) {
+                let mut features = ext.features.empty();
+                merge(&mut *features, &*ext.features);
+                intersect(&mut *features, have);
+                result.extensions.push(ExtensionFeatures {
+                    extension: ext.extension,
+                    features,
+                });
+            }
+        }
+        result
+    }
+
+    /// Adds every feature of `other` to this set.
+    pub fn union(&mut self, other: &DeviceFeatures) {
+        merge(&mut self.core, &other.core);
+        merge(&mut self.vulkan_11, &other.vulkan_11);
+        merge(&mut self.vulkan_12, &other.vulkan_12);
+        for ext in &other.extensions {
+            let mut features = ext.features.empty();
+            merge(&mut *features, &*ext.features);
+            self.add_extension_features(ext.extension, features);
+        }
+    }
+
+    /// Returns the number of features in this set.
+    pub fn count(&self) -> usize {
+        count(&self.core)
+            + count(&self.vulkan_11)
+            + count(&self.vulkan_12)
+            + self
+                .extensions
+                .iter()
+                .map(|ext| count(&*ext.features))
+                .sum::<usize>()
+    }
+}
+
+fn compare<T, U>(prefix: &str, wanted: &T, have: &U, missing: &mut Vec<String>)
+where
+    T: FeatureStruct + ?Sized,
+    U: FeatureStruct + ?Sized,
+{
+    for ((name, wanted), (_, have)) in wanted.fields().into_iter().zip(have.fields()) {
+        if wanted == vk::TRUE && have != vk::TRUE {
+            missing.push(format!("{}.{}", prefix, name));
+        }
+    }
+}
+
+fn count<T: FeatureStruct + ?Sized>(features: &T) -> usize {
+    features
+        .fields()
+        .iter()
+        .filter(|(_, value)| *value == vk::TRUE)
+        .count()
+}
+
+fn merge<T, U>(target: &mut T, source: &U)
+where
+    T: FeatureStruct + ?Sized,
+    U: FeatureStruct + ?Sized,
+{
+    for (target, (_, source)) in target.fields_mut().into_iter().zip(source.fields()) {
+        if source == vk::TRUE {
+            *target = vk::TRUE;
+        }
+    }
+}
+
+fn intersect<T, U>(target: &mut T, other: &U)
+where
+    T: FeatureStruct + ?Sized,
+    U: FeatureStruct + ?Sized,
+{
+    for (target, (_, other)) in target.fields_mut().into_iter().zip(other.fields()) {
+        if other != vk::TRUE {
+            *target = vk::FALSE;
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+
+/// Forces a specific physical device instead of the one with the highest score.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// Index into the list of devices as enumerated by the instance.
+    Index(usize),
+    /// The `deviceUUID` reported by `VkPhysicalDeviceIDProperties`.
+    Uuid([u8; 16]),
+    /// Case insensitive substring of the device name.
+    Name(String),
+}
+
+impl DeviceSelector {
+    /// Parses a plain integer as an index, 32 hex digits (optionally dashed) as a UUID and
+    /// anything else as a name substring.
+    pub fn parse(value: &str) -> Self {
+        // UUIDs come first, 32 decimal digits with leading zeros would otherwise be an index.
+        if let Some(uuid) = parse_uuid(value) {
+            return Self::Uuid(uuid);
+        }
+        if let Ok(index) = value.parse() {
+            return Self::Index(index);
+        }
+        Self::Name(value.to_lowercase())
+    }
+
+    pub fn matches(&self, index: usize, name: &str, uuid: &[u8; 16]) -> bool {
+        match self {
+            Self::Index(selected) => *selected == index,
+            Self::Uuid(selected) => selected == uuid,
+            Self::Name(selected) => name.to_lowercase().contains(selected.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "index {}", index),
+            Self::Uuid(uuid) => write!(f, "UUID {}", format_uuid(uuid)),
+            Self::Name(name) => write!(f, "name {:?}", name),
+        }
+    }
+}
+
+fn parse_uuid(value: &str) -> Option<[u8; 16]> {
+    let digits = value.replace('-', "");
+    if digits.len() != 32 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut uuid = [0; 16];
+    for (i, byte) in uuid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(uuid)
+}
+
+/// Formats a UUID in the usual 8-4-4-4-12 form.
+pub fn format_uuid(uuid: &[u8; 16]) -> String {
+    let hex = uuid
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; 16] = [
+        0x67, 0x9c, 0x8a, 0x21, 0x0d, 0x4f, 0x5e, 0xb3, 0x9a, 0x61, 0x2c, 0x70, 0xe5, 0x18, 0x4b,
+        0xd2,
+    ];
+
+    #[test]
+    fn parses_index() {
+        assert_eq!(DeviceSelector::parse("0"), DeviceSelector::Index(0));
+        assert_eq!(DeviceSelector::parse("12"), DeviceSelector::Index(12));
+    }
+
+    #[test]
+    fn parses_dashed_uuid() {
+        assert_eq!(
+            DeviceSelector::parse("679c8a21-0d4f-5eb3-9a61-2c70e5184bd2"),
+            DeviceSelector::Uuid(UUID)
+        );
+        assert_eq!(
+            DeviceSelector::parse("679C8A21-0D4F-5EB3-9A61-2C70E5184BD2"),
+            DeviceSelector::Uuid(UUID)
+        );
+    }
+
+    #[test]
+    fn parses_undashed_uuid() {
+        assert_eq!(
+            DeviceSelector::parse("679c8a210d4f5eb39a612c70e5184bd2"),
+            DeviceSelector::Uuid(UUID)
+        );
+        // Only decimal digits, which would also parse as an index.
+        assert_eq!(
+            DeviceSelector::parse("00000000000000000000000000000001"),
+            DeviceSelector::Uuid([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1])
+        );
+    }
+
+    #[test]
+    fn parses_anything_else_as_name() {
+        assert_eq!(
+            DeviceSelector::parse("GeForce RTX"),
+            DeviceSelector::Name("geforce rtx".to_owned())
+        );
+        // One hex digit short of a UUID.
+        assert_eq!(
+            DeviceSelector::parse("679c8a21-0d4f-5eb3-9a61-2c70e5184bd"),
+            DeviceSelector::Name("679c8a21-0d4f-5eb3-9a61-2c70e5184bd".to_owned())
+        );
+        // Not hexadecimal.
+        assert_eq!(
+            DeviceSelector::parse("679c8a21-0d4f-5eb3-9a61-2c70e5184bdg"),
+            DeviceSelector::Name("679c8a21-0d4f-5eb3-9a61-2c70e5184bdg".to_owned())
+        );
+        assert_eq!(
+            DeviceSelector::parse("-1"),
+            DeviceSelector::Name("-1".to_owned())
+        );
+    }
+
+    #[test]
+    fn matches_devices() {
+        let name = "AMD Radeon RX 6800 XT (RADV NAVI21)";
+        assert!(DeviceSelector::Index(1).matches(1, name, &UUID));
+        assert!(!DeviceSelector::Index(0).matches(1, name, &UUID));
+        assert!(DeviceSelector::Uuid(UUID).matches(0, name, &UUID));
+        assert!(!DeviceSelector::Uuid([0; 16]).matches(0, name, &UUID));
+        assert!(DeviceSelector::parse("Radeon").matches(0, name, &UUID));
+        assert!(DeviceSelector::parse("radv").matches(0, name, &UUID));
+        assert!(!DeviceSelector::parse("llvmpipe").matches(0, name, &UUID));
+    }
+
+    #[test]
+    fn formats_uuid() {
+        assert_eq!(format_uuid(&UUID), "679c8a21-0d4f-5eb3-9a61-2c70e5184bd2");
+        assert_eq!(
+            DeviceSelector::parse(&format_uuid(&UUID)),
+            DeviceSelector::Uuid(UUID)
+        );
+    }
+}
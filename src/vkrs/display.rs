@@ -0,0 +1,211 @@
+//! Window modes, and choosing the monitor and video mode to go fullscreen on.
+
+use std::fmt;
+use winit::{
+    event_loop::EventLoop,
+    monitor::{MonitorHandle, VideoMode},
+    window::Fullscreen,
+};
+
+/// Pixels per inch at a scale factor of 1, which the scale factors of the platforms are relative
+/// to.
+pub const BASE_DPI: f64 = 96.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Maximized,
+    /// A borderless window covering the monitor, at the resolution of the desktop.
+    Borderless,
+    /// Exclusive fullscreen, which switches the monitor to the video mode.
+    Exclusive,
+}
+
+impl WindowMode {
+    pub const ALL: [Self; 4] = [
+        Self::Windowed,
+        Self::Maximized,
+        Self::Borderless,
+        Self::Exclusive,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Windowed => "windowed",
+            Self::Maximized => "maximized",
+            Self::Borderless => "borderless",
+            Self::Exclusive => "exclusive",
+        }
+    }
+
+    pub fn is_fullscreen(self) -> bool {
+        matches!(self, Self::Borderless | Self::Exclusive)
+    }
+}
+
+/// The monitor to go fullscreen on, instead of the one the window is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MonitorSelector {
+    /// Index into the list of monitors as enumerated by the event loop.
+    Index(usize),
+    /// Case insensitive substring of the monitor name.
+    Name(String),
+}
+
+impl MonitorSelector {
+    /// Parses a plain integer as an index and anything else as a name substring.
+    pub fn parse(value: &str) -> Self {
+        match value.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(value.to_lowercase()),
+        }
+    }
+
+    fn matches(&self, index: usize, monitor: &MonitorHandle) -> bool {
+        match self {
+            Self::Index(selected) => *selected == index,
+            Self::Name(selected) => monitor
+                .name()
+                .is_some_and(|name| name.to_lowercase().contains(selected.as_str())),
+        }
+    }
+}
+
+impl fmt::Display for MonitorSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "index {}", index),
+            Self::Name(name) => write!(f, "name {:?}", name),
+        }
+    }
+}
+
+/// The video mode to use in exclusive fullscreen, such as `1920x1080` or `1920x1080@144`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VideoModeRequest {
+    pub width: u32,
+    pub height: u32,
+    /// Defaults to the highest refresh rate of the resolution.
+    pub refresh_rate: Option<u16>,
+}
+
+impl VideoModeRequest {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (size, refresh_rate) = match value.split_once('@') {
+            Some((size, refresh_rate)) => (size, Some(refresh_rate.parse().ok()?)),
+            None => (value, None),
+        };
+        let (width, height) = size.split_once('x')?;
+        Some(Self {
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+            refresh_rate,
+        })
+    }
+
+    fn matches(&self, mode: &VideoMode) -> bool {
+        mode.size().width == self.width
+            && mode.size().height == self.height
+            && self
+                .refresh_rate
+                .map_or(true, |refresh_rate| mode.refresh_rate() == refresh_rate)
+    }
+}
+
+impl fmt::Display for VideoModeRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if let Some(refresh_rate) = self.refresh_rate {
+            write!(f, "@{}", refresh_rate)?;
+        }
+        Ok(())
+    }
+}
+
+/// The fullscreen mode to start in if `mode` is fullscreen, and to switch to with Alt+Enter
+/// otherwise. That is borderless unless `mode` is exclusive.
+///
+/// Without a monitor borderless fullscreen uses the one the window is on, and exclusive fullscreen
+/// the primary monitor. Without a video mode, or one the monitor does not support, exclusive
+/// fullscreen uses the largest with the highest refresh rate.
+pub fn choose_fullscreen(
+    event_loop: &EventLoop<()>,
+    mode: WindowMode,
+    monitor: Option<&MonitorSelector>,
+    video_mode: Option<&VideoModeRequest>,
+) -> Fullscreen {
+    for (index, monitor) in event_loop.available_monitors().enumerate() {
+        log::debug!(target: "vkrs",
+                    "Monitor {}: {} at {:?}, {}x{}",
+                    index,
+                    monitor.name().unwrap_or_default(),
+                    monitor.position(),
+                    monitor.size().width,
+                    monitor.size().height);
+    }
+    let monitor = monitor.and_then(|selector| {
+        let monitor = event_loop
+            .available_monitors()
+            .enumerate()
+            .find(|(index, monitor)| selector.matches(*index, monitor))
+            .map(|(_, monitor)| monitor);
+        if monitor.is_none() {
+            log::warn!(target: "vkrs",
+                       "No monitor matches {}, using the default one",
+                       selector);
+        }
+        monitor
+    });
+    if mode != WindowMode::Exclusive {
+        return Fullscreen::Borderless(monitor);
+    }
+
+    // Exclusive fullscreen needs a monitor to pick the video mode from.
+    let monitor = match monitor.or_else(|| event_loop.primary_monitor()) {
+        Some(monitor) => monitor,
+        None => {
+            log::warn!(target: "vkrs",
+                       "No monitor to choose a video mode from, using borderless fullscreen");
+            return Fullscreen::Borderless(None);
+        }
+    };
+    let largest = |a: &VideoMode, b: &VideoMode| {
+        let area = |mode: &VideoMode| mode.size().width * mode.size().height;
+        (area(a), a.refresh_rate(), a.bit_depth()).cmp(&(area(b), b.refresh_rate(), b.bit_depth()))
+    };
+    let requested = video_mode.and_then(|request| {
+        let mode = monitor
+            .video_modes()
+            .filter(|mode| request.matches(mode))
+            .max_by(largest);
+        if mode.is_none() {
+            log::warn!(target: "vkrs",
+                       "{} does not support video mode {}, using its largest",
+                       monitor.name().unwrap_or_default(),
+                       request);
+        }
+        mode
+    });
+    match requested.or_else(|| monitor.video_modes().max_by(largest)) {
+        Some(video_mode) => {
+            log::info!(target: "vkrs",
+                       "Using video mode {}x{}@{} on {}",
+                       video_mode.size().width,
+                       video_mode.size().height,
+                       video_mode.refresh_rate(),
+                       monitor.name().unwrap_or_default());
+            Fullscreen::Exclusive(video_mode)
+        }
+        None => {
+            log::warn!(target: "vkrs",
+                       "{} has no video modes, using borderless fullscreen",
+                       monitor.name().unwrap_or_default());
+            Fullscreen::Borderless(Some(monitor))
+        }
+    }
+}
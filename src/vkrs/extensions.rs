@@ -1,90 +1,231 @@
+use super::validation::ValidationConfig;
+
 use ash::{extensions::ext::DebugUtils, vk};
+use raw_window_handle::HasRawWindowHandle;
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 
-#[cfg(target_os = "linux")]
-pub fn get_required_extensions(window: &winit::window::Window) -> Vec<&'static CStr> {
-    use crate::vkrs::validation;
-
-    let mut extensions = ash_window::enumerate_required_extensions(window)
-        .expect("Failed to enumerate required extensions for window.");
-    if validation::ENABLE_VALIDATION_LAYERS {
-        unsafe {
-            extensions.push(CStr::from_ptr(DebugUtils::name().as_ptr()));
+/// Extensions requested from the instance or a device. Required extensions must be available,
+/// optional extensions are enabled only when they are.
+#[derive(Clone, Default)]
+pub struct ExtensionRequests {
+    required: Vec<&'static CStr>,
+    optional: Vec<&'static CStr>,
+}
+
+impl ExtensionRequests {
+    pub fn require(&mut self, extension: &'static CStr) {
+        if !self.required.contains(&extension) {
+            self.required.push(extension);
+        }
+        self.optional.retain(|ext| *ext != extension);
+    }
+
+    pub fn request(&mut self, extension: &'static CStr) {
+        if !self.required.contains(&extension) && !self.optional.contains(&extension) {
+            self.optional.push(extension);
         }
     }
+
+    pub fn optional(&self) -> &[&'static CStr] {
+        &self.optional
+    }
+
+    /// Returns the extensions to enable given the `available` ones, or the missing required
+    /// extensions.
+    pub fn negotiate(&self, available: &[&CStr]) -> Result<EnabledExtensions, Vec<&'static CStr>> {
+        let missing = self
+            .required
+            .iter()
+            .filter(|ext| !available.contains(ext))
+            .copied()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+
+        let mut names = self.required.clone();
+        names.extend(self.optional.iter().filter(|ext| available.contains(ext)));
+        Ok(EnabledExtensions { names })
+    }
+}
+
+/// The extensions that were actually enabled, for code paths that depend on optional ones.
+#[derive(Clone, Debug, Default)]
+pub struct EnabledExtensions {
+    names: Vec<&'static CStr>,
+}
+
+impl EnabledExtensions {
+    pub fn contains(&self, extension: &'static CStr) -> bool {
+        self.names.contains(&extension)
+    }
+
+    pub fn names(&self) -> &[&'static CStr] {
+        &self.names
+    }
+}
+
+/// Joins extension names for diagnostics.
+pub fn join_names(extensions: &[&CStr]) -> String {
     extensions
+        .iter()
+        .map(|ext| ext.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
-pub fn check_required_extensions(
-    entry: &ash::Entry,
-    required_extensions: &[&'static CStr],
-) -> Result<(), String> {
+/// Without a window the instance is created without any surface extensions, for headless
+/// rendering.
+#[cfg(target_os = "linux")]
+pub fn get_instance_extension_requests(
+    window: Option<&dyn HasRawWindowHandle>,
+    validation: &ValidationConfig,
+) -> ExtensionRequests {
+    let mut requests = ExtensionRequests::default();
+    if let Some(window) = window {
+        // ash-window takes the raw-window-handle 0.3 trait, which the reference implements
+        // through the 0.4 trait.
+        let window_extensions = ash_window::enumerate_required_extensions(&window)
+            .expect("Failed to enumerate required extensions for window.");
+        for extension in window_extensions {
+            requests.require(extension);
+        }
+        // Adds the HDR and wide gamut color spaces to the surface formats.
+        requests.request(vk::ExtSwapchainColorspaceFn::name());
+    }
+    // Debug utils also provide object names and labels for tools like RenderDoc, so they are
+    // enabled whenever they are available.
+    if validation.enabled {
+        requests.require(DebugUtils::name());
+    } else {
+        requests.request(DebugUtils::name());
+    }
+    // Provided by the validation layer, it is only available when the layer is enabled.
+    if !validation.feature_enables().is_empty() {
+        requests.request(vk::ExtValidationFeaturesFn::name());
+    }
+    requests
+}
+
+pub fn get_available_instance_extensions(entry: &ash::Entry) -> Vec<CString> {
     let available_extensions = entry
         .enumerate_instance_extension_properties()
         .expect("Failed to get available extensions.");
 
     log::debug!(target: "vkrs", "available extensions:");
-    for ext in &available_extensions {
-        log::debug!(target: "vkrs", "\t{}", unsafe {
-            CStr::from_ptr(ext.extension_name.as_ptr())
+    available_extensions
+        .iter()
+        .map(|ext| {
+            let name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
+            log::debug!(target: "vkrs", "\t{}", name
                 .to_str()
-                .expect("Failed to convert string to UTF-8.")
-        });
-    }
-
-    let mut missing_extensions = String::new();
-    for required_extension in required_extensions.iter() {
-        unsafe {
-            let extension_found = available_extensions.iter().find(|ext| {
-                let available_extension = CStr::from_ptr(ext.extension_name.as_ptr());
-                *required_extension == available_extension
-            });
-            if extension_found.is_none() {
-                if !missing_extensions.is_empty() {
-                    missing_extensions.push_str(", ");
-                }
-                missing_extensions.push_str(
-                    required_extension
-                        .to_str()
-                        .expect("Failed to convert extension name to UTF-8."),
-                );
-            }
-        }
-    }
-    if missing_extensions.is_empty() {
-        Ok(())
-    } else {
-        Err(missing_extensions)
-    }
+                .expect("Failed to convert string to UTF-8."));
+            name.to_owned()
+        })
+        .collect()
 }
 
-pub fn get_required_device_extensions() -> [&'static CStr; 1] {
-    [ash::extensions::khr::Swapchain::name()]
+/// Extensions every device needs, and optional ones that are used when available. Only devices
+/// that `present` need swapchains.
+pub fn get_device_extension_requests(present: bool) -> ExtensionRequests {
+    let mut requests = ExtensionRequests::default();
+    if present {
+        requests.require(ash::extensions::khr::Swapchain::name());
+    }
+    requests.request(vk::ExtMemoryBudgetFn::name());
+    requests
 }
 
-pub fn check_device_extension_support(
+pub fn get_available_device_extensions(
     instance: &ash::Instance,
     device: vk::PhysicalDevice,
-) -> bool {
-    let required_extensions = get_required_device_extensions();
-
+) -> Vec<CString> {
     let extension_properties = unsafe {
         instance
             .enumerate_device_extension_properties(device)
             .expect("Failed to enumerate device extension properties.")
     };
+    extension_properties
+        .iter()
+        .map(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) }.to_owned())
+        .collect()
+}
 
-    for required_extension in required_extensions.iter() {
-        let found = extension_properties.iter().any(|ext| {
-            let extension_name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
-            *required_extension == extension_name
-        });
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if !found {
-            return false;
-        }
+    fn swapchain() -> &'static CStr {
+        ash::extensions::khr::Swapchain::name()
+    }
+
+    fn memory_budget() -> &'static CStr {
+        vk::ExtMemoryBudgetFn::name()
     }
 
-    true
+    fn colorspace() -> &'static CStr {
+        vk::ExtSwapchainColorspaceFn::name()
+    }
+
+    #[test]
+    fn require_wins_over_request() {
+        let mut requests = ExtensionRequests::default();
+        requests.request(swapchain());
+        requests.require(swapchain());
+        assert!(requests.optional().is_empty());
+        // Requesting a required extension does not make it optional again.
+        requests.request(swapchain());
+        assert!(requests.optional().is_empty());
+        assert!(requests.negotiate(&[]).is_err());
+    }
+
+    #[test]
+    fn requests_each_extension_once() {
+        let mut requests = ExtensionRequests::default();
+        requests.request(memory_budget());
+        requests.request(memory_budget());
+        requests.require(swapchain());
+        requests.require(swapchain());
+        assert_eq!(requests.optional(), [memory_budget()]);
+        let enabled = requests.negotiate(&[swapchain(), memory_budget()]).unwrap();
+        assert_eq!(enabled.names(), [swapchain(), memory_budget()]);
+    }
+
+    #[test]
+    fn reports_missing_required_extensions() {
+        let mut requests = ExtensionRequests::default();
+        requests.require(swapchain());
+        requests.require(colorspace());
+        requests.request(memory_budget());
+        let missing = requests.negotiate(&[colorspace(), memory_budget()]).err();
+        assert_eq!(missing, Some(vec![swapchain()]));
+    }
+
+    #[test]
+    fn enables_only_available_optional_extensions() {
+        let mut requests = ExtensionRequests::default();
+        requests.require(swapchain());
+        requests.request(memory_budget());
+        requests.request(colorspace());
+        let enabled = requests.negotiate(&[colorspace(), swapchain()]).unwrap();
+        assert_eq!(enabled.names(), [swapchain(), colorspace()]);
+        assert!(enabled.contains(colorspace()));
+        assert!(!enabled.contains(memory_budget()));
+    }
+
+    #[test]
+    fn device_extensions_need_swapchains_only_to_present() {
+        assert!(get_device_extension_requests(true).negotiate(&[]).is_err());
+        let enabled = get_device_extension_requests(false).negotiate(&[]).unwrap();
+        assert!(enabled.names().is_empty());
+    }
+
+    #[test]
+    fn joins_names() {
+        assert_eq!(
+            join_names(&[swapchain(), memory_budget()]),
+            "VK_KHR_swapchain, VK_EXT_memory_budget"
+        );
+    }
 }
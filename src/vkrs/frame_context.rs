@@ -0,0 +1,133 @@
+use super::gpu_profiler::{FrameTimestamps, GpuProfiler};
+use super::handles::{Buffer, Device, Semaphore};
+use super::uniform_buffer_object::UniformBufferObject;
+use super::vulkan;
+
+use ash::vk;
+
+use std::{
+    mem::{align_of, size_of},
+    os::raw::c_void,
+    rc::Rc,
+};
+
+/// Everything that is used by a single frame in flight. None of it depends on the swapchain, so
+/// the frame contexts survive swapchain recreation.
+pub struct FrameContext {
+    pub command_buffer: vk::CommandBuffer,
+    _uniform_buffer: Buffer,
+    // The uniform buffer stays mapped for the lifetime of the frame context, the mapping goes away
+    // when its memory is freed.
+    uniform_buffer_ptr: *mut c_void,
+    pub descriptor_set: vk::DescriptorSet,
+    pub image_available_semaphore: Semaphore,
+    pub render_finished_semaphore: Semaphore,
+    /// Only present if the GPU profiler is available.
+    pub timestamps: Option<FrameTimestamps>,
+    /// GPU progress value of the last submission made for this frame, waiting for it takes the
+    /// place of an in-flight fence.
+    pub timeline_value: u64,
+}
+
+impl FrameContext {
+    /// Creates one frame context per frame in flight, allocating their descriptor sets from
+    /// `descriptor_pool`. The command buffers and descriptor sets are freed together with their
+    /// pools.
+    pub fn create_frame_contexts(
+        device: &Rc<Device>,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        command_pool: vk::CommandPool,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        gpu_profiler: Option<&GpuProfiler>,
+        frames_in_flight: u32,
+    ) -> Vec<Self> {
+        let command_buffers =
+            vulkan::create_command_buffers(device, command_pool, frames_in_flight);
+        let uniform_buffers =
+            vulkan::create_uniform_buffers(device, memory_properties, frames_in_flight);
+        let uniform_buffer_handles = uniform_buffers
+            .iter()
+            .map(|buffer| buffer.handle())
+            .collect::<Vec<_>>();
+        let descriptor_sets = vulkan::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            &uniform_buffer_handles,
+        );
+        let (image_available_semaphores, render_finished_semaphores) =
+            vulkan::create_sync_objects(device, frames_in_flight);
+
+        command_buffers
+            .into_iter()
+            .zip(uniform_buffers)
+            .zip(descriptor_sets)
+            .zip(
+                image_available_semaphores
+                    .into_iter()
+                    .zip(render_finished_semaphores),
+            )
+            .enumerate()
+            .map(
+                |(
+                    index,
+                    (
+                        ((command_buffer, uniform_buffer), descriptor_set),
+                        (image_available_semaphore, render_finished_semaphore),
+                    ),
+                )| {
+                    device.set_object_name(
+                        command_buffer,
+                        &format!("frame {} command buffer", index),
+                    );
+                    uniform_buffer.set_name(&format!("frame {} uniform buffer", index));
+                    device.set_object_name(
+                        descriptor_set,
+                        &format!("frame {} descriptor set", index),
+                    );
+                    image_available_semaphore
+                        .set_name(&format!("frame {} image available semaphore", index));
+                    render_finished_semaphore
+                        .set_name(&format!("frame {} render finished semaphore", index));
+                    let timestamps = gpu_profiler.map(|gpu_profiler| {
+                        let timestamps = gpu_profiler.create_frame_timestamps();
+                        timestamps.set_name(&format!("frame {} timestamp query pool", index));
+                        timestamps
+                    });
+                    let uniform_buffer_ptr = unsafe {
+                        device
+                            .map_memory(
+                                uniform_buffer.memory(),
+                                0,
+                                size_of::<UniformBufferObject>() as vk::DeviceSize,
+                                vk::MemoryMapFlags::empty(),
+                            )
+                            .expect("Failed to map uniform buffer memory.")
+                    };
+                    Self {
+                        command_buffer,
+                        _uniform_buffer: uniform_buffer,
+                        uniform_buffer_ptr,
+                        descriptor_set,
+                        image_available_semaphore,
+                        render_finished_semaphore,
+                        timestamps,
+                        timeline_value: 0,
+                    }
+                },
+            )
+            .collect()
+    }
+
+    /// The frame must not be in use by the GPU when this is called.
+    pub fn write_uniform_buffer(&self, ubo: UniformBufferObject) {
+        let ubos = [ubo];
+        let size = size_of::<UniformBufferObject>() as vk::DeviceSize;
+        unsafe {
+            let mut align =
+                ash::util::Align::new(self.uniform_buffer_ptr, align_of::<f32>() as _, size);
+            align.copy_from_slice(&ubos);
+        }
+    }
+}
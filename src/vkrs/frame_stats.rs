@@ -0,0 +1,142 @@
+//! Frame time and per-frame command statistics.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    ops::AddAssign,
+    time::{Duration, Instant},
+};
+
+/// Number of frames the statistics are taken over.
+const WINDOW: usize = 240;
+
+/// What was recorded into a frame's command buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub pipeline_binds: u32,
+    pub descriptor_binds: u32,
+}
+
+impl AddAssign for DrawStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.pipeline_binds += other.pipeline_binds;
+        self.descriptor_binds += other.descriptor_binds;
+    }
+}
+
+/// Statistics over the most recent frames, see
+/// [`Renderer::frame_stats`](super::Renderer::frame_stats).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStatsSummary {
+    pub fps: f64,
+    pub average_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    /// Average time per frame the CPU spent waiting for the GPU.
+    pub gpu_wait_ms: f64,
+    /// What was recorded into the most recent frame.
+    pub draws: DrawStats,
+}
+
+impl fmt::Display for FrameStatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} fps, {:.2} ms (p50 {:.2}, p95 {:.2}, p99 {:.2}, max {:.2}), GPU wait {:.2} ms, \
+             {} draws, {} triangles, {} pipeline binds, {} descriptor binds",
+            self.fps,
+            self.average_ms,
+            self.p50_ms,
+            self.p95_ms,
+            self.p99_ms,
+            self.max_ms,
+            self.gpu_wait_ms,
+            self.draws.draw_calls,
+            self.draws.triangles,
+            self.draws.pipeline_binds,
+            self.draws.descriptor_binds
+        )
+    }
+}
+
+/// Collects the time between frames, the time spent waiting for the GPU, and the [`DrawStats`]
+/// of every frame.
+pub struct FrameStats {
+    last_frame_start: Option<Instant>,
+    frame_times: VecDeque<Duration>,
+    gpu_waits: VecDeque<Duration>,
+    last_draws: DrawStats,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self {
+            last_frame_start: None,
+            frame_times: VecDeque::with_capacity(WINDOW),
+            gpu_waits: VecDeque::with_capacity(WINDOW),
+            last_draws: DrawStats::default(),
+        }
+    }
+
+    /// Called at the start of every frame, the frame time is the time between two starts.
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame_start) = self.last_frame_start {
+            push_sample(&mut self.frame_times, now - last_frame_start);
+        }
+        self.last_frame_start = Some(now);
+    }
+
+    pub fn end_frame(&mut self, gpu_wait: Duration, draws: DrawStats) {
+        push_sample(&mut self.gpu_waits, gpu_wait);
+        self.last_draws = draws;
+    }
+
+    /// The most recent frame times, oldest first.
+    pub fn frame_times(&self) -> impl ExactSizeIterator<Item = Duration> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    pub fn summary(&self) -> FrameStatsSummary {
+        let mut sorted = self
+            .frame_times
+            .iter()
+            .map(|time| time.as_secs_f64() * 1000.0)
+            .collect::<Vec<_>>();
+        if sorted.is_empty() {
+            return FrameStatsSummary::default();
+        }
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        let average_ms = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        let gpu_wait_ms = self
+            .gpu_waits
+            .iter()
+            .map(|wait| wait.as_secs_f64() * 1000.0)
+            .sum::<f64>()
+            / self.gpu_waits.len().max(1) as f64;
+        FrameStatsSummary {
+            fps: 1000.0 / average_ms,
+            average_ms,
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: sorted[sorted.len() - 1],
+            gpu_wait_ms,
+            draws: self.last_draws,
+        }
+    }
+}
+
+fn push_sample(samples: &mut VecDeque<Duration>, sample: Duration) {
+    if samples.len() == WINDOW {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
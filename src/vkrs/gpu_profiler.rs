@@ -0,0 +1,321 @@
+//! GPU timings from timestamp queries written around passes.
+//!
+//! Every frame in flight records its scopes into a [`FrameTimestamps`] of its own, and the
+//! [`GpuProfiler`] reads them back once the frame has finished executing, so reading never stalls.
+
+use super::handles::{Device, QueryPool};
+use super::trace;
+
+use ash::vk;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Scopes beyond this many in a single frame are not timed.
+const MAX_SCOPES: u32 = 32;
+
+/// Number of frames the logged averages are taken over.
+const AVERAGE_WINDOW: usize = 120;
+
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Scope {
+    name: &'static str,
+    parent: Option<usize>,
+}
+
+/// The timestamp queries of a single frame in flight. Scope `i` writes queries `2 * i` and
+/// `2 * i + 1`.
+pub struct FrameTimestamps {
+    query_pool: QueryPool,
+    scopes: Vec<Scope>,
+    // Indices of the open scopes, `None` for scopes that did not fit in the query pool.
+    open: Vec<Option<usize>>,
+    // When the frame was recorded, the GPU cannot have started it any earlier.
+    recorded_at: Instant,
+}
+
+impl FrameTimestamps {
+    fn new(device: &Rc<Device>) -> Self {
+        let create_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2 * MAX_SCOPES);
+        let query_pool = unsafe {
+            device
+                .create_query_pool(&create_info, None)
+                .expect("Failed to create timestamp query pool.")
+        };
+        Self {
+            query_pool: QueryPool::new(device, query_pool),
+            scopes: Vec::new(),
+            open: Vec::new(),
+            recorded_at: Instant::now(),
+        }
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.query_pool.set_name(name);
+    }
+
+    /// Resets the queries, must be recorded outside of a render pass before any scope.
+    pub fn begin_frame(&mut self, device: &Device, command_buffer: vk::CommandBuffer) {
+        self.scopes.clear();
+        self.open.clear();
+        self.recorded_at = Instant::now();
+        unsafe {
+            device.cmd_reset_query_pool(
+                command_buffer,
+                self.query_pool.handle(),
+                0,
+                2 * MAX_SCOPES,
+            );
+        }
+    }
+
+    /// Opens a scope nested in the innermost open scope, which must be closed with
+    /// [`Self::end_scope`] in the same command buffer.
+    pub fn begin_scope(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        name: &'static str,
+    ) {
+        if self.scopes.len() as u32 == MAX_SCOPES {
+            self.open.push(None);
+            return;
+        }
+        let index = self.scopes.len();
+        self.scopes.push(Scope {
+            name,
+            parent: self.open.iter().rev().flatten().next().copied(),
+        });
+        self.open.push(Some(index));
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.query_pool.handle(),
+                2 * index as u32,
+            );
+        }
+    }
+
+    pub fn end_scope(&mut self, device: &Device, command_buffer: vk::CommandBuffer) {
+        let index = match self.open.pop().expect("No GPU scope is open.") {
+            Some(index) => index,
+            None => return,
+        };
+        unsafe {
+            device.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.query_pool.handle(),
+                2 * index as u32 + 1,
+            );
+        }
+    }
+}
+
+/// The time a scope took on the GPU, together with the scopes nested in it.
+#[derive(Clone, Debug)]
+pub struct GpuTiming {
+    pub name: &'static str,
+    pub milliseconds: f64,
+    pub children: Vec<GpuTiming>,
+}
+
+impl GpuTiming {
+    /// The time of every scope in `timings` and the scopes nested in them, keyed by the names of
+    /// the scope and its parents, separated by slashes.
+    pub fn flatten(timings: &[GpuTiming]) -> Vec<(String, f64)> {
+        let mut paths = Vec::new();
+        Self::paths(timings, "", &mut paths);
+        paths
+    }
+
+    fn paths(timings: &[GpuTiming], prefix: &str, paths: &mut Vec<(String, f64)>) {
+        for timing in timings {
+            let path = format!("{}/{}", prefix, timing.name);
+            Self::paths(&timing.children, &path, paths);
+            paths.push((path, timing.milliseconds));
+        }
+    }
+}
+
+#[derive(Default)]
+struct RollingAverage {
+    samples: VecDeque<f64>,
+    sum: f64,
+}
+
+impl RollingAverage {
+    fn add(&mut self, sample: f64) {
+        if self.samples.len() == AVERAGE_WINDOW {
+            self.sum -= self.samples.pop_front().unwrap();
+        }
+        self.samples.push_back(sample);
+        self.sum += sample;
+    }
+
+    fn average(&self) -> f64 {
+        self.sum / self.samples.len().max(1) as f64
+    }
+}
+
+/// Turns the timestamps of finished frames into [`GpuTiming`] trees, and logs their rolling
+/// averages to the `gpu` target.
+pub struct GpuProfiler {
+    device: Rc<Device>,
+    // Nanoseconds per timestamp tick.
+    timestamp_period: f64,
+    // Timestamps only have `timestampValidBits` significant bits and wrap around.
+    timestamp_mask: u64,
+    latest: Vec<GpuTiming>,
+    // Number of frames collected so far, `latest` changes whenever this does.
+    collected_frames: u64,
+    // Keyed by the paths from `GpuTiming::flatten`.
+    averages: HashMap<String, RollingAverage>,
+    last_log: Instant,
+    // Where the last frame added to the trace ended on the CPU timeline.
+    last_traced_end: Option<Instant>,
+}
+
+impl GpuProfiler {
+    /// Returns `None` if the queue family does not support timestamps.
+    pub fn new(
+        device: &Rc<Device>,
+        physical_device: vk::PhysicalDevice,
+        queue_family_index: u32,
+    ) -> Option<Self> {
+        let instance = device.instance();
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let valid_bits = queue_families[queue_family_index as usize].timestamp_valid_bits;
+        if valid_bits == 0 {
+            log::warn!(target: "vkrs",
+                       "Timestamps are not supported by the graphics queue, GPU profiling is disabled");
+            return None;
+        }
+        Some(Self {
+            device: Rc::clone(device),
+            timestamp_period: properties.limits.timestamp_period as f64,
+            timestamp_mask: u64::MAX >> (64 - valid_bits),
+            latest: Vec::new(),
+            collected_frames: 0,
+            averages: HashMap::new(),
+            last_log: Instant::now(),
+            last_traced_end: None,
+        })
+    }
+
+    pub fn create_frame_timestamps(&self) -> FrameTimestamps {
+        FrameTimestamps::new(&self.device)
+    }
+
+    /// The timings of the most recent frame that has been collected.
+    pub fn latest(&self) -> &[GpuTiming] {
+        &self.latest
+    }
+
+    pub fn collected_frames(&self) -> u64 {
+        self.collected_frames
+    }
+
+    /// Reads back the scopes of a frame, which must have finished executing on the GPU.
+    pub fn collect(&mut self, timestamps: &mut FrameTimestamps) {
+        if timestamps.scopes.is_empty() {
+            return;
+        }
+        let mut results = vec![0u64; 2 * timestamps.scopes.len()];
+        unsafe {
+            self.device
+                .get_query_pool_results(
+                    timestamps.query_pool.handle(),
+                    0,
+                    results.len() as u32,
+                    &mut results,
+                    vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+                )
+                .expect("Failed to get timestamp query results.");
+        }
+
+        let milliseconds = results
+            .chunks_exact(2)
+            .map(|query| {
+                let ticks = query[1].wrapping_sub(query[0]) & self.timestamp_mask;
+                ticks as f64 * self.timestamp_period / 1_000_000.0
+            })
+            .collect::<Vec<_>>();
+        self.latest = Self::children(&timestamps.scopes, &milliseconds, None);
+        self.collected_frames += 1;
+        if trace::is_capturing() {
+            self.trace(timestamps, &results);
+        }
+        timestamps.scopes.clear();
+
+        for (path, milliseconds) in GpuTiming::flatten(&self.latest) {
+            self.averages.entry(path).or_default().add(milliseconds);
+        }
+
+        if self.last_log.elapsed() >= LOG_INTERVAL {
+            self.last_log = Instant::now();
+            self.log_averages(self.latest(), "", 0);
+        }
+    }
+
+    /// Adds the scopes to the trace. GPU timestamps are in a time domain of their own, so the
+    /// frame is placed where it started at the earliest, after it was recorded and after the
+    /// previous frame ended, which is only approximately where it ran.
+    fn trace(&mut self, timestamps: &FrameTimestamps, results: &[u64]) {
+        let to_duration = |ticks: u64| {
+            Duration::from_nanos(
+                ((ticks & self.timestamp_mask) as f64 * self.timestamp_period) as u64,
+            )
+        };
+        let first = results[0];
+        let start = match self.last_traced_end {
+            Some(last_end) => timestamps.recorded_at.max(last_end),
+            None => timestamps.recorded_at,
+        };
+        let mut end = start;
+        for (scope, query) in timestamps.scopes.iter().zip(results.chunks_exact(2)) {
+            let scope_start = start + to_duration(query[0].wrapping_sub(first));
+            let duration = to_duration(query[1].wrapping_sub(query[0]));
+            trace::gpu_event(scope.name, scope_start, duration);
+            end = end.max(scope_start + duration);
+        }
+        self.last_traced_end = Some(end);
+    }
+
+    fn children(scopes: &[Scope], milliseconds: &[f64], parent: Option<usize>) -> Vec<GpuTiming> {
+        scopes
+            .iter()
+            .enumerate()
+            .filter(|(_, scope)| scope.parent == parent)
+            .map(|(index, scope)| GpuTiming {
+                name: scope.name,
+                milliseconds: milliseconds[index],
+                children: Self::children(scopes, milliseconds, Some(index)),
+            })
+            .collect()
+    }
+
+    fn log_averages(&self, timings: &[GpuTiming], prefix: &str, depth: usize) {
+        for timing in timings {
+            let path = format!("{}/{}", prefix, timing.name);
+            let average = &self.averages[&path];
+            log::info!(target: "gpu",
+                       "{:indent$}{}: {:.3} ms (average over {} frames)",
+                       "",
+                       timing.name,
+                       average.average(),
+                       average.samples.len(),
+                       indent = 2 * depth);
+            self.log_averages(&timing.children, &path, depth + 1);
+        }
+    }
+}
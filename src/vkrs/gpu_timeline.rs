@@ -0,0 +1,270 @@
+//! Tracks the progress of the GPU through the work submitted to it.
+//!
+//! The renderer submits everything to its graphics queue, and only the graphics queue is
+//! supported. [`Submission::wait_value`] is meant for dependencies on other queues, but in fence
+//! mode it is resolved by waiting on the CPU before submitting, and nothing submits to another
+//! queue yet.
+
+use super::device_features::DeviceRequirements;
+use super::handles::{Device, Fence, Semaphore};
+use super::trace;
+
+use ash::vk;
+
+use std::{
+    collections::VecDeque,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// How the completion of submitted work is tracked on the CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameSyncMode {
+    /// Every submission signals a binary fence of its own.
+    #[default]
+    Fences,
+    /// Every submission signals the next value of a single Vulkan 1.2 timeline semaphore. Falls
+    /// back to fences if the device does not support timeline semaphores.
+    TimelineSemaphore,
+}
+
+impl FrameSyncMode {
+    pub const ALL: [Self; 2] = [Self::Fences, Self::TimelineSemaphore];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fences => "fences",
+            Self::TimelineSemaphore => "timeline",
+        }
+    }
+}
+
+/// Work to submit through [`GpuTimeline::submit`].
+#[derive(Default)]
+pub struct Submission<'a> {
+    pub command_buffers: &'a [vk::CommandBuffer],
+    pub wait_semaphores: &'a [vk::Semaphore],
+    pub wait_stages: &'a [vk::PipelineStageFlags],
+    pub signal_semaphores: &'a [vk::Semaphore],
+    /// GPU progress value, typically from a submission to another queue, that has to be reached
+    /// before the given stage of this submission may execute.
+    pub wait_value: Option<(u64, vk::PipelineStageFlags)>,
+}
+
+/// A single monotonic "GPU progress" value shared by all submissions. Every submission made
+/// through [`GpuTimeline::submit`] is assigned the next value, and other subsystems can poll or
+/// wait for a value to know when the work behind it has finished executing.
+pub struct GpuTimeline {
+    device: Rc<Device>,
+    mode: FrameSyncMode,
+    // Only used in timeline semaphore mode.
+    semaphore: Option<Semaphore>,
+    last_submitted_value: u64,
+    completed_value: u64,
+    // Only used in fence mode, pending fences are kept in submission order.
+    pending_fences: VecDeque<(u64, Fence)>,
+    free_fences: Vec<Fence>,
+    // Time spent blocked in `wait` since the last `take_wait_time`.
+    wait_time: Duration,
+}
+
+impl GpuTimeline {
+    /// Timeline semaphores are optional, [`FrameSyncMode::Fences`] is used if they are missing.
+    pub fn request_features(mode: FrameSyncMode, requirements: &mut DeviceRequirements) {
+        if mode == FrameSyncMode::TimelineSemaphore {
+            requirements.optional.vulkan_12.timeline_semaphore = vk::TRUE;
+        }
+    }
+
+    /// Falls back to [`FrameSyncMode::Fences`] if timeline semaphores were not enabled on the
+    /// device.
+    pub fn new(device: &Rc<Device>, mut mode: FrameSyncMode) -> Self {
+        if mode == FrameSyncMode::TimelineSemaphore
+            && device.capabilities().features.vulkan_12.timeline_semaphore != vk::TRUE
+        {
+            log::warn!(target: "vkrs",
+                       "Timeline semaphores are not supported by the device, falling back to fences");
+            mode = FrameSyncMode::Fences;
+        }
+
+        let semaphore = match mode {
+            FrameSyncMode::Fences => None,
+            FrameSyncMode::TimelineSemaphore => {
+                let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+                    .semaphore_type(vk::SemaphoreType::TIMELINE)
+                    .initial_value(0);
+                let semaphore_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+                let semaphore = unsafe {
+                    device
+                        .create_semaphore(&semaphore_info, None)
+                        .expect("Failed to create timeline semaphore.")
+                };
+                let semaphore = Semaphore::new(device, semaphore);
+                semaphore.set_name("gpu timeline semaphore");
+                Some(semaphore)
+            }
+        };
+        log::debug!(target: "vkrs", "Tracking GPU progress with {:?}", mode);
+
+        Self {
+            device: Rc::clone(device),
+            mode,
+            semaphore,
+            last_submitted_value: 0,
+            completed_value: 0,
+            pending_fences: VecDeque::new(),
+            free_fences: Vec::new(),
+            wait_time: Duration::ZERO,
+        }
+    }
+
+    pub fn last_submitted_value(&self) -> u64 {
+        self.last_submitted_value
+    }
+
+    /// Submits `submission` to `queue` and returns the GPU progress value it signals.
+    pub fn submit(&mut self, queue: vk::Queue, submission: &Submission) -> u64 {
+        let value = self.last_submitted_value + 1;
+
+        match self.mode {
+            FrameSyncMode::Fences => {
+                // Fences cannot be waited on by the GPU, so dependencies are resolved on the CPU.
+                if let Some((wait_value, _)) = submission.wait_value {
+                    self.wait(wait_value);
+                }
+
+                let fence = self.free_fences.pop().unwrap_or_else(|| {
+                    let fence_info = vk::FenceCreateInfo::builder();
+                    let fence = unsafe {
+                        self.device
+                            .create_fence(&fence_info, None)
+                            .expect("Failed to create fence.")
+                    };
+                    let fence = Fence::new(&self.device, fence);
+                    fence.set_name("submission fence");
+                    fence
+                });
+                let submit_info = vk::SubmitInfo::builder()
+                    .wait_semaphores(submission.wait_semaphores)
+                    .wait_dst_stage_mask(submission.wait_stages)
+                    .command_buffers(submission.command_buffers)
+                    .signal_semaphores(submission.signal_semaphores)
+                    .build();
+                unsafe {
+                    self.device
+                        .queue_submit(queue, &[submit_info], fence.handle())
+                        .expect("Failed to submit command buffers.")
+                };
+                self.pending_fences.push_back((value, fence));
+            }
+            FrameSyncMode::TimelineSemaphore => {
+                let semaphore = self.semaphore.as_ref().unwrap().handle();
+                // Binary semaphores ignore their values, but the counts have to match.
+                let mut wait_semaphores = submission.wait_semaphores.to_vec();
+                let mut wait_stages = submission.wait_stages.to_vec();
+                let mut wait_values = vec![0; wait_semaphores.len()];
+                if let Some((wait_value, wait_stage)) = submission.wait_value {
+                    wait_semaphores.push(semaphore);
+                    wait_stages.push(wait_stage);
+                    wait_values.push(wait_value);
+                }
+                let mut signal_semaphores = submission.signal_semaphores.to_vec();
+                let mut signal_values = vec![0; signal_semaphores.len()];
+                signal_semaphores.push(semaphore);
+                signal_values.push(value);
+
+                let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+                    .wait_semaphore_values(&wait_values)
+                    .signal_semaphore_values(&signal_values);
+                let submit_info = vk::SubmitInfo::builder()
+                    .wait_semaphores(&wait_semaphores)
+                    .wait_dst_stage_mask(&wait_stages)
+                    .command_buffers(submission.command_buffers)
+                    .signal_semaphores(&signal_semaphores)
+                    .push_next(&mut timeline_info)
+                    .build();
+                unsafe {
+                    self.device
+                        .queue_submit(queue, &[submit_info], vk::Fence::null())
+                        .expect("Failed to submit command buffers.")
+                };
+            }
+        }
+
+        self.last_submitted_value = value;
+        value
+    }
+
+    /// Returns the highest value for which all work has finished executing.
+    pub fn completed_value(&mut self) -> u64 {
+        match self.mode {
+            FrameSyncMode::Fences => {
+                while let Some((value, fence)) = self.pending_fences.front() {
+                    let signaled = unsafe { self.device.get_fence_status(fence.handle()).unwrap() };
+                    if !signaled {
+                        break;
+                    }
+                    let value = *value;
+                    let (_, fence) = self.pending_fences.pop_front().unwrap();
+                    unsafe { self.device.reset_fences(&[fence.handle()]).unwrap() };
+                    self.free_fences.push(fence);
+                    self.completed_value = value;
+                }
+            }
+            FrameSyncMode::TimelineSemaphore => {
+                let semaphore = self.semaphore.as_ref().unwrap().handle();
+                self.completed_value = unsafe {
+                    self.device
+                        .get_semaphore_counter_value(semaphore)
+                        .expect("Failed to get timeline semaphore value.")
+                };
+            }
+        }
+        self.completed_value
+    }
+
+    /// Blocks until the work behind `value` has finished executing.
+    pub fn wait(&mut self, value: u64) {
+        if value <= self.completed_value {
+            return;
+        }
+        let _span = trace::span("wait for GPU");
+        let start = Instant::now();
+
+        match self.mode {
+            FrameSyncMode::Fences => {
+                let fence = self
+                    .pending_fences
+                    .iter()
+                    .find(|(pending_value, _)| *pending_value >= value)
+                    .map(|(_, fence)| fence.handle());
+                if let Some(fence) = fence {
+                    unsafe {
+                        self.device
+                            .wait_for_fences(&[fence], true, u64::MAX)
+                            .unwrap()
+                    };
+                }
+            }
+            FrameSyncMode::TimelineSemaphore => {
+                let semaphores = [self.semaphore.as_ref().unwrap().handle()];
+                let values = [value];
+                let wait_info = vk::SemaphoreWaitInfo::builder()
+                    .semaphores(&semaphores)
+                    .values(&values);
+                unsafe { self.device.wait_semaphores(&wait_info, u64::MAX).unwrap() };
+            }
+        }
+        self.wait_time += start.elapsed();
+        self.completed_value();
+    }
+
+    /// Returns the time spent blocked waiting for the GPU since the last call.
+    pub fn take_wait_time(&mut self) -> Duration {
+        std::mem::take(&mut self.wait_time)
+    }
+}
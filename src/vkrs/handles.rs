@@ -0,0 +1,510 @@
+//! Owning wrappers for Vulkan handles.
+//!
+//! Every wrapper destroys its handle when dropped, and keeps its parent alive through an `Rc`, so
+//! a parent can never be destroyed before its children regardless of drop order.
+
+use super::capabilities::Capabilities;
+use super::extensions::EnabledExtensions;
+use super::validation::ValidationConfig;
+use super::validation_sink::{ValidationReporter, ValidationSink};
+
+use ash::vk;
+use serde::Serialize;
+
+use std::{cell::Cell, ffi::CString, ops::Deref, rc::Rc};
+
+/// The Vulkan instance together with the entry it was loaded through.
+pub struct Instance {
+    entry: ash::Entry,
+    instance: ash::Instance,
+    extensions: EnabledExtensions,
+    validation: ValidationConfig,
+}
+
+impl Instance {
+    /// `validation` is the validation the instance was actually created with.
+    pub fn new(
+        entry: ash::Entry,
+        instance: ash::Instance,
+        extensions: EnabledExtensions,
+        validation: ValidationConfig,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            entry,
+            instance,
+            extensions,
+            validation,
+        })
+    }
+
+    pub fn entry(&self) -> &ash::Entry {
+        &self.entry
+    }
+
+    pub fn extensions(&self) -> &EnabledExtensions {
+        &self.extensions
+    }
+
+    pub fn validation(&self) -> &ValidationConfig {
+        &self.validation
+    }
+}
+
+impl Deref for Instance {
+    type Target = ash::Instance;
+
+    fn deref(&self) -> &Self::Target {
+        &self.instance
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe { self.instance.destroy_instance(None) };
+        log::debug!(target: "vkrs", "Deinitialized");
+    }
+}
+
+pub struct DebugMessenger {
+    _instance: Rc<Instance>,
+    loader: ash::extensions::ext::DebugUtils,
+    handle: vk::DebugUtilsMessengerEXT,
+    // Dropped after the messenger is destroyed, since the callback points to it.
+    reporter: Option<Box<ValidationReporter>>,
+}
+
+impl DebugMessenger {
+    /// `handle` may be null when validation is disabled, the loader is kept regardless.
+    pub fn new(
+        instance: &Rc<Instance>,
+        loader: ash::extensions::ext::DebugUtils,
+        handle: vk::DebugUtilsMessengerEXT,
+    ) -> Self {
+        Self {
+            _instance: Rc::clone(instance),
+            loader,
+            handle,
+            reporter: None,
+        }
+    }
+
+    /// `reporter` must be the one passed to the callback of the messenger.
+    pub fn with_reporter(mut self, reporter: Box<ValidationReporter>) -> Self {
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// Adds a sink for validation messages. Returns `false` if there is no messenger, which is the
+    /// case when validation is disabled.
+    pub fn add_sink(&self, sink: Box<dyn ValidationSink>) -> bool {
+        match &self.reporter {
+            Some(reporter) => {
+                reporter.add_sink(sink);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Drop for DebugMessenger {
+    fn drop(&mut self) {
+        if self.handle != vk::DebugUtilsMessengerEXT::null() {
+            unsafe { self.loader.destroy_debug_utils_messenger(self.handle, None) };
+        }
+    }
+}
+
+pub struct Surface {
+    _instance: Rc<Instance>,
+    loader: ash::extensions::khr::Surface,
+    handle: vk::SurfaceKHR,
+}
+
+impl Surface {
+    pub fn new(
+        instance: &Rc<Instance>,
+        loader: ash::extensions::khr::Surface,
+        handle: vk::SurfaceKHR,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            _instance: Rc::clone(instance),
+            loader,
+            handle,
+        })
+    }
+
+    pub fn loader(&self) -> &ash::extensions::khr::Surface {
+        &self.loader
+    }
+
+    pub fn handle(&self) -> vk::SurfaceKHR {
+        self.handle
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        unsafe { self.loader.destroy_surface(self.handle, None) };
+    }
+}
+
+/// The logical device together with what was enabled on it.
+pub struct Device {
+    instance: Rc<Instance>,
+    device: ash::Device,
+    capabilities: Capabilities,
+    // Only loaded if `VK_EXT_debug_utils` is enabled, naming and labels do nothing otherwise.
+    debug_utils: Option<ash::extensions::ext::DebugUtils>,
+    memory_usage: Cell<MemoryUsage>,
+}
+
+/// Device memory allocated for buffers and images. Swapchain images are owned by the presentation
+/// engine and not included.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct MemoryUsage {
+    pub allocated_bytes: vk::DeviceSize,
+    /// The most that was allocated at any one time.
+    pub peak_allocated_bytes: vk::DeviceSize,
+}
+
+impl Device {
+    pub fn new(
+        instance: &Rc<Instance>,
+        device: ash::Device,
+        capabilities: Capabilities,
+    ) -> Rc<Self> {
+        let debug_utils = instance
+            .extensions()
+            .contains(ash::extensions::ext::DebugUtils::name())
+            .then(|| ash::extensions::ext::DebugUtils::new(instance.entry(), instance));
+        Rc::new(Self {
+            instance: Rc::clone(instance),
+            device,
+            capabilities,
+            debug_utils,
+            memory_usage: Cell::new(MemoryUsage::default()),
+        })
+    }
+
+    pub fn instance(&self) -> &Rc<Instance> {
+        &self.instance
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory_usage.get()
+    }
+
+    fn track_allocation(&self, size: vk::DeviceSize) {
+        let mut usage = self.memory_usage.get();
+        usage.allocated_bytes += size;
+        usage.peak_allocated_bytes = usage.peak_allocated_bytes.max(usage.allocated_bytes);
+        self.memory_usage.set(usage);
+    }
+
+    fn track_free(&self, size: vk::DeviceSize) {
+        let mut usage = self.memory_usage.get();
+        usage.allocated_bytes -= size;
+        self.memory_usage.set(usage);
+    }
+
+    /// Names `handle` for validation messages and capture tools. Names are only for debugging,
+    /// so failing to set one is logged rather than fatal.
+    pub fn set_object_name<T: vk::Handle>(&self, handle: T, name: &str) {
+        let debug_utils = match &self.debug_utils {
+            Some(debug_utils) => debug_utils,
+            None => return,
+        };
+        let name = debug_name(name);
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name);
+        let result =
+            unsafe { debug_utils.debug_utils_set_object_name(self.device.handle(), &name_info) };
+        if let Err(error) = result {
+            log::warn!(target: "vkrs",
+                       "Failed to name {:?} {:?}: {}",
+                       T::TYPE,
+                       name,
+                       error);
+        }
+    }
+
+    /// Opens a labeled region in `command_buffer`, which must be closed with [`Self::end_label`].
+    pub fn begin_label(&self, command_buffer: vk::CommandBuffer, name: &str) {
+        if let Some(debug_utils) = &self.debug_utils {
+            let name = debug_name(name);
+            let label = vk::DebugUtilsLabelEXT::builder().label_name(&name);
+            unsafe { debug_utils.cmd_begin_debug_utils_label(command_buffer, &label) };
+        }
+    }
+
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils) = &self.debug_utils {
+            unsafe { debug_utils.cmd_end_debug_utils_label(command_buffer) };
+        }
+    }
+}
+
+/// Converts a debug name to a C string. Names can come from users of the renderer, so NUL bytes
+/// are replaced instead of failing.
+fn debug_name(name: &str) -> CString {
+    CString::new(name.replace('\0', "\u{fffd}")).expect("NUL bytes were replaced.")
+}
+
+impl Deref for Device {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_device(None) };
+    }
+}
+
+/// A handle that is created from, and destroyed through, the logical device.
+pub trait DeviceChild: Copy + vk::Handle {
+    /// # Safety
+    ///
+    /// The handle must have been created from `device` and must no longer be in use by the GPU.
+    unsafe fn destroy(self, device: &ash::Device);
+}
+
+macro_rules! device_child {
+    ($($handle:ty => $destroy:ident),* $(,)?) => {
+        $(
+            impl DeviceChild for $handle {
+                unsafe fn destroy(self, device: &ash::Device) {
+                    device.$destroy(self, None);
+                }
+            }
+        )*
+    };
+}
+
+device_child! {
+    vk::CommandPool => destroy_command_pool,
+    vk::DescriptorPool => destroy_descriptor_pool,
+    vk::DescriptorSetLayout => destroy_descriptor_set_layout,
+    vk::Fence => destroy_fence,
+    vk::Framebuffer => destroy_framebuffer,
+    vk::ImageView => destroy_image_view,
+    vk::Pipeline => destroy_pipeline,
+    vk::PipelineLayout => destroy_pipeline_layout,
+    vk::QueryPool => destroy_query_pool,
+    vk::RenderPass => destroy_render_pass,
+    vk::Sampler => destroy_sampler,
+    vk::Semaphore => destroy_semaphore,
+    vk::ShaderModule => destroy_shader_module,
+}
+
+/// Owns a single handle created from the logical device.
+pub struct Owned<T: DeviceChild> {
+    device: Rc<Device>,
+    handle: T,
+}
+
+impl<T: DeviceChild> Owned<T> {
+    pub fn new(device: &Rc<Device>, handle: T) -> Self {
+        Self {
+            device: Rc::clone(device),
+            handle,
+        }
+    }
+
+    pub fn handle(&self) -> T {
+        self.handle
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
+}
+
+impl<T: DeviceChild> Drop for Owned<T> {
+    fn drop(&mut self) {
+        unsafe { self.handle.destroy(&self.device) };
+    }
+}
+
+pub type CommandPool = Owned<vk::CommandPool>;
+pub type DescriptorPool = Owned<vk::DescriptorPool>;
+pub type DescriptorSetLayout = Owned<vk::DescriptorSetLayout>;
+pub type Fence = Owned<vk::Fence>;
+pub type Framebuffer = Owned<vk::Framebuffer>;
+pub type ImageView = Owned<vk::ImageView>;
+pub type Pipeline = Owned<vk::Pipeline>;
+pub type PipelineLayout = Owned<vk::PipelineLayout>;
+pub type QueryPool = Owned<vk::QueryPool>;
+pub type RenderPass = Owned<vk::RenderPass>;
+pub type Sampler = Owned<vk::Sampler>;
+pub type Semaphore = Owned<vk::Semaphore>;
+pub type ShaderModule = Owned<vk::ShaderModule>;
+
+/// A buffer together with the memory bound to it.
+pub struct Buffer {
+    device: Rc<Device>,
+    handle: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+}
+
+impl Buffer {
+    /// `size` is the size of the allocated memory, which may be larger than the buffer.
+    pub fn new(
+        device: &Rc<Device>,
+        handle: vk::Buffer,
+        memory: vk::DeviceMemory,
+        size: vk::DeviceSize,
+    ) -> Self {
+        device.track_allocation(size);
+        Self {
+            device: Rc::clone(device),
+            handle,
+            memory,
+            size,
+        }
+    }
+
+    pub fn handle(&self) -> vk::Buffer {
+        self.handle
+    }
+
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn memory_size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    /// Names both the buffer and its memory.
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+        self.device
+            .set_object_name(self.memory, &format!("{} memory", name));
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.handle, None);
+            self.device.free_memory(self.memory, None);
+        }
+        self.device.track_free(self.size);
+    }
+}
+
+/// An image together with the memory bound to it.
+pub struct Image {
+    device: Rc<Device>,
+    handle: vk::Image,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+}
+
+impl Image {
+    /// `size` is the size of the allocated memory.
+    pub fn new(
+        device: &Rc<Device>,
+        handle: vk::Image,
+        memory: vk::DeviceMemory,
+        size: vk::DeviceSize,
+    ) -> Self {
+        device.track_allocation(size);
+        Self {
+            device: Rc::clone(device),
+            handle,
+            memory,
+            size,
+        }
+    }
+
+    pub fn handle(&self) -> vk::Image {
+        self.handle
+    }
+
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    /// Names both the image and its memory.
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+        self.device
+            .set_object_name(self.memory, &format!("{} memory", name));
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image(self.handle, None);
+            self.device.free_memory(self.memory, None);
+        }
+        self.device.track_free(self.size);
+    }
+}
+
+/// A swapchain, which is a child of both the device and the surface.
+pub struct Swapchain {
+    device: Rc<Device>,
+    _surface: Rc<Surface>,
+    loader: ash::extensions::khr::Swapchain,
+    handle: vk::SwapchainKHR,
+}
+
+impl Swapchain {
+    pub fn new(
+        device: &Rc<Device>,
+        surface: &Rc<Surface>,
+        loader: ash::extensions::khr::Swapchain,
+        handle: vk::SwapchainKHR,
+    ) -> Self {
+        Self {
+            device: Rc::clone(device),
+            _surface: Rc::clone(surface),
+            loader,
+            handle,
+        }
+    }
+
+    pub fn loader(&self) -> &ash::extensions::khr::Swapchain {
+        &self.loader
+    }
+
+    pub fn handle(&self) -> vk::SwapchainKHR {
+        self.handle
+    }
+
+    pub fn set_name(&self, name: &str) {
+        self.device.set_object_name(self.handle, name);
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        unsafe { self.loader.destroy_swapchain(self.handle, None) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_nul_bytes_in_debug_names() {
+        assert_eq!(debug_name("mesh:quad").to_str(), Ok("mesh:quad"));
+        assert_eq!(debug_name("mesh:a\0b").to_str(), Ok("mesh:a\u{fffd}b"));
+    }
+}
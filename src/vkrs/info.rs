@@ -0,0 +1,515 @@
+use super::device_features::DeviceRequirements;
+use super::device_selector::{format_uuid, DeviceSelector};
+use super::gpu_timeline::FrameSyncMode;
+use super::handles::Surface;
+use super::renderer::Renderer;
+use super::swapchain;
+use super::validation::ValidationConfig;
+use super::vulkan;
+
+use ash::vk;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use std::{ffi::CStr, fmt::Write};
+
+/// Everything the engine sees of the Vulkan implementation, for attaching to bug reports.
+#[derive(Serialize)]
+struct InfoReport {
+    vkrs_version: &'static str,
+    instance_version: String,
+    instance_extensions: Vec<String>,
+    devices: Vec<DeviceReport>,
+}
+
+#[derive(Serialize)]
+struct DeviceReport {
+    index: usize,
+    name: String,
+    uuid: String,
+    device_type: String,
+    api_version: String,
+    driver_version: u32,
+    vendor_id: u32,
+    device_id: u32,
+    /// The score given by `rate_physical_device`, if the device is suitable.
+    score: Option<u32>,
+    rejections: Vec<String>,
+    /// Whether the engine would use this device with the current `--gpu`/`VKRS_GPU` selection.
+    selected: bool,
+    #[serde(skip)]
+    matches_selector: bool,
+    limits: Map<String, Value>,
+    memory_heaps: Vec<MemoryHeapReport>,
+    memory_types: Vec<MemoryTypeReport>,
+    queue_families: Vec<QueueFamilyReport>,
+    extensions: Vec<ExtensionReport>,
+    surface_formats: Vec<SurfaceFormatReport>,
+    present_modes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct MemoryHeapReport {
+    size: u64,
+    flags: String,
+}
+
+#[derive(Serialize)]
+struct MemoryTypeReport {
+    heap_index: u32,
+    property_flags: String,
+}
+
+#[derive(Serialize)]
+struct QueueFamilyReport {
+    index: u32,
+    flags: String,
+    queue_count: u32,
+    timestamp_valid_bits: u32,
+    present_support: bool,
+}
+
+#[derive(Serialize)]
+struct ExtensionReport {
+    name: String,
+    spec_version: u32,
+}
+
+#[derive(Serialize)]
+struct SurfaceFormatReport {
+    format: String,
+    color_space: String,
+}
+
+/// Prints the report for every physical device to stdout, as text or as JSON.
+pub fn print_device_info(
+    name: &'static str,
+    window: &winit::window::Window,
+    device_selector: Option<&DeviceSelector>,
+    validation: &ValidationConfig,
+    frame_sync: FrameSyncMode,
+    json: bool,
+) {
+    let report = collect_report(name, window, device_selector, validation, frame_sync);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Failed to serialize device info.")
+        );
+    } else {
+        print!("{}", format_text(&report));
+    }
+}
+
+fn collect_report(
+    name: &'static str,
+    window: &winit::window::Window,
+    device_selector: Option<&DeviceSelector>,
+    validation: &ValidationConfig,
+    frame_sync: FrameSyncMode,
+) -> InfoReport {
+    let (instance, _debug_messenger, surface) =
+        Renderer::create_instance_and_surface(name, Some(window), validation);
+    let surface = surface.expect("Instances created with a window have a surface.");
+    let requirements = Renderer::device_requirements(frame_sync, instance.validation(), true);
+
+    let instance_version = match instance.entry().try_enumerate_instance_version() {
+        Ok(Some(version)) => format_version(version),
+        _ => format_version(vk::API_VERSION_1_0),
+    };
+    let instance_extensions = instance
+        .extensions()
+        .names()
+        .iter()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .collect();
+
+    let physical_devices = unsafe {
+        instance
+            .enumerate_physical_devices()
+            .expect("Failed to enumerate physical devices.")
+    };
+    let mut devices = physical_devices
+        .iter()
+        .enumerate()
+        .map(|(index, physical_device)| {
+            device_report(
+                &instance,
+                &surface,
+                &requirements,
+                device_selector,
+                index,
+                *physical_device,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Mirrors `select_physical_device`, which takes the first device with the highest score.
+    let selected = devices
+        .iter()
+        .filter(|device| device.matches_selector)
+        .filter_map(|device| device.score.map(|score| (device.index, score)))
+        .fold(
+            None,
+            |best: Option<(usize, u32)>, (index, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((index, score)),
+            },
+        );
+    if let Some((index, _)) = selected {
+        devices[index].selected = true;
+    }
+
+    InfoReport {
+        vkrs_version: env!("CARGO_PKG_VERSION"),
+        instance_version,
+        instance_extensions,
+        devices,
+    }
+}
+
+fn device_report(
+    instance: &ash::Instance,
+    surface: &Surface,
+    requirements: &DeviceRequirements,
+    device_selector: Option<&DeviceSelector>,
+    index: usize,
+    physical_device: vk::PhysicalDevice,
+) -> DeviceReport {
+    let (name, uuid) = vulkan::physical_device_identity(instance, physical_device);
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let (score, rejections) = match vulkan::rate_physical_device(
+        instance,
+        Some(surface),
+        physical_device,
+        requirements,
+    ) {
+        Ok((score, _, _)) => (Some(score), Vec::new()),
+        Err(rejections) => (None, rejections),
+    };
+
+    let memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+    let memory_heaps = memory_properties.memory_heaps
+        [..memory_properties.memory_heap_count as usize]
+        .iter()
+        .map(|heap| MemoryHeapReport {
+            size: heap.size,
+            flags: format!("{:?}", heap.flags),
+        })
+        .collect();
+    let memory_types = memory_properties.memory_types
+        [..memory_properties.memory_type_count as usize]
+        .iter()
+        .map(|memory_type| MemoryTypeReport {
+            heap_index: memory_type.heap_index,
+            property_flags: format!("{:?}", memory_type.property_flags),
+        })
+        .collect();
+
+    let queue_families =
+        unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+            .iter()
+            .enumerate()
+            .map(|(index, queue_family)| {
+                let index = index as u32;
+                let present_support = unsafe {
+                    surface
+                        .loader()
+                        .get_physical_device_surface_support(
+                            physical_device,
+                            index,
+                            surface.handle(),
+                        )
+                        .unwrap_or(false)
+                };
+                QueueFamilyReport {
+                    index,
+                    flags: format!("{:?}", queue_family.queue_flags),
+                    queue_count: queue_family.queue_count,
+                    timestamp_valid_bits: queue_family.timestamp_valid_bits,
+                    present_support,
+                }
+            })
+            .collect();
+
+    let extensions = unsafe {
+        instance
+            .enumerate_device_extension_properties(physical_device)
+            .expect("Failed to enumerate device extension properties.")
+    }
+    .iter()
+    .map(|ext| ExtensionReport {
+        name: unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned(),
+        spec_version: ext.spec_version,
+    })
+    .collect();
+
+    let support_details =
+        swapchain::SupportDetails::new(physical_device, surface.loader(), surface.handle());
+    let surface_formats = support_details
+        .formats
+        .iter()
+        .map(|format| SurfaceFormatReport {
+            format: format!("{:?}", format.format),
+            color_space: format!("{:?}", format.color_space),
+        })
+        .collect();
+    let present_modes = support_details
+        .present_modes
+        .iter()
+        .map(|present_mode| format!("{:?}", present_mode))
+        .collect();
+
+    DeviceReport {
+        index,
+        selected: false,
+        score,
+        rejections,
+        matches_selector: device_selector
+            .map_or(true, |selector| selector.matches(index, &name, &uuid)),
+        name,
+        uuid: format_uuid(&uuid),
+        device_type: format!("{:?}", properties.device_type),
+        api_version: format_version(properties.api_version),
+        driver_version: properties.driver_version,
+        vendor_id: properties.vendor_id,
+        device_id: properties.device_id,
+        limits: limits(&properties.limits),
+        memory_heaps,
+        memory_types,
+        queue_families,
+        extensions,
+        surface_formats,
+        present_modes,
+    }
+}
+
+fn format_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+macro_rules! limits {
+    ($limits:expr, [$($field:ident),* $(,)?], flags: [$($flags:ident),* $(,)?]) => {{
+        let mut map = Map::new();
+        $(map.insert(stringify!($field).to_owned(), json!($limits.$field));)*
+        $(map.insert(stringify!($flags).to_owned(), json!(format!("{:?}", $limits.$flags)));)*
+        map
+    }};
+}
+
+fn limits(limits: &vk::PhysicalDeviceLimits) -> Map<String, Value> {
+    limits!(
+        limits,
+        [
+            max_image_dimension1_d,
+            max_image_dimension2_d,
+            max_image_dimension3_d,
+            max_image_dimension_cube,
+            max_image_array_layers,
+            max_texel_buffer_elements,
+            max_uniform_buffer_range,
+            max_storage_buffer_range,
+            max_push_constants_size,
+            max_memory_allocation_count,
+            max_sampler_allocation_count,
+            buffer_image_granularity,
+            sparse_address_space_size,
+            max_bound_descriptor_sets,
+            max_per_stage_descriptor_samplers,
+            max_per_stage_descriptor_uniform_buffers,
+            max_per_stage_descriptor_storage_buffers,
+            max_per_stage_descriptor_sampled_images,
+            max_per_stage_descriptor_storage_images,
+            max_per_stage_descriptor_input_attachments,
+            max_per_stage_resources,
+            max_descriptor_set_samplers,
+            max_descriptor_set_uniform_buffers,
+            max_descriptor_set_uniform_buffers_dynamic,
+            max_descriptor_set_storage_buffers,
+            max_descriptor_set_storage_buffers_dynamic,
+            max_descriptor_set_sampled_images,
+            max_descriptor_set_storage_images,
+            max_descriptor_set_input_attachments,
+            max_vertex_input_attributes,
+            max_vertex_input_bindings,
+            max_vertex_input_attribute_offset,
+            max_vertex_input_binding_stride,
+            max_vertex_output_components,
+            max_tessellation_generation_level,
+            max_tessellation_patch_size,
+            max_tessellation_control_per_vertex_input_components,
+            max_tessellation_control_per_vertex_output_components,
+            max_tessellation_control_per_patch_output_components,
+            max_tessellation_control_total_output_components,
+            max_tessellation_evaluation_input_components,
+            max_tessellation_evaluation_output_components,
+            max_geometry_shader_invocations,
+            max_geometry_input_components,
+            max_geometry_output_components,
+            max_geometry_output_vertices,
+            max_geometry_total_output_components,
+            max_fragment_input_components,
+            max_fragment_output_attachments,
+            max_fragment_dual_src_attachments,
+            max_fragment_combined_output_resources,
+            max_compute_shared_memory_size,
+            max_compute_work_group_count,
+            max_compute_work_group_invocations,
+            max_compute_work_group_size,
+            sub_pixel_precision_bits,
+            sub_texel_precision_bits,
+            mipmap_precision_bits,
+            max_draw_indexed_index_value,
+            max_draw_indirect_count,
+            max_sampler_lod_bias,
+            max_sampler_anisotropy,
+            max_viewports,
+            max_viewport_dimensions,
+            viewport_bounds_range,
+            viewport_sub_pixel_bits,
+            min_memory_map_alignment,
+            min_texel_buffer_offset_alignment,
+            min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment,
+            min_texel_offset,
+            max_texel_offset,
+            min_texel_gather_offset,
+            max_texel_gather_offset,
+            min_interpolation_offset,
+            max_interpolation_offset,
+            sub_pixel_interpolation_offset_bits,
+            max_framebuffer_width,
+            max_framebuffer_height,
+            max_framebuffer_layers,
+            max_color_attachments,
+            max_sample_mask_words,
+            timestamp_compute_and_graphics,
+            timestamp_period,
+            max_clip_distances,
+            max_cull_distances,
+            max_combined_clip_and_cull_distances,
+            discrete_queue_priorities,
+            point_size_range,
+            line_width_range,
+            point_size_granularity,
+            line_width_granularity,
+            strict_lines,
+            standard_sample_locations,
+            optimal_buffer_copy_offset_alignment,
+            optimal_buffer_copy_row_pitch_alignment,
+            non_coherent_atom_size,
+        ],
+        flags: [
+            framebuffer_color_sample_counts,
+            framebuffer_depth_sample_counts,
+            framebuffer_stencil_sample_counts,
+            framebuffer_no_attachments_sample_counts,
+            sampled_image_color_sample_counts,
+            sampled_image_integer_sample_counts,
+            sampled_image_depth_sample_counts,
+            sampled_image_stencil_sample_counts,
+            storage_image_sample_counts,
+        ]
+    )
+}
+
+fn format_text(report: &InfoReport) -> String {
+    let mut out = String::new();
+    write_text(report, &mut out).expect("Failed to format device info.");
+    out
+}
+
+fn write_text(report: &InfoReport, out: &mut String) -> std::fmt::Result {
+    writeln!(out, "vkrs {}", report.vkrs_version)?;
+    writeln!(out, "Vulkan instance {}", report.instance_version)?;
+    writeln!(
+        out,
+        "Instance extensions: {}",
+        report.instance_extensions.join(", ")
+    )?;
+
+    for device in &report.devices {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Device {}: {}{}",
+            device.index,
+            device.name,
+            if device.selected { " (selected)" } else { "" }
+        )?;
+        writeln!(out, "  UUID:           {}", device.uuid)?;
+        writeln!(out, "  Type:           {}", device.device_type)?;
+        writeln!(out, "  API version:    {}", device.api_version)?;
+        writeln!(out, "  Driver version: {:#x}", device.driver_version)?;
+        writeln!(
+            out,
+            "  Vendor/device:  {:#06x}/{:#06x}",
+            device.vendor_id, device.device_id
+        )?;
+        match device.score {
+            Some(score) => writeln!(out, "  Score:          {}", score)?,
+            None => writeln!(out, "  Rejected:       {}", device.rejections.join(", "))?,
+        }
+
+        writeln!(out, "  Limits:")?;
+        for (name, value) in &device.limits {
+            writeln!(out, "    {}: {}", name, value)?;
+        }
+
+        writeln!(out, "  Memory heaps:")?;
+        for (index, heap) in device.memory_heaps.iter().enumerate() {
+            writeln!(
+                out,
+                "    {}: {} MiB, {}",
+                index,
+                heap.size / (1024 * 1024),
+                heap.flags
+            )?;
+        }
+        writeln!(out, "  Memory types:")?;
+        for (index, memory_type) in device.memory_types.iter().enumerate() {
+            writeln!(
+                out,
+                "    {}: heap {}, {}",
+                index, memory_type.heap_index, memory_type.property_flags
+            )?;
+        }
+
+        writeln!(out, "  Queue families:")?;
+        for queue_family in &device.queue_families {
+            writeln!(
+                out,
+                "    {}: {}, {} queues, {} timestamp bits{}",
+                queue_family.index,
+                queue_family.flags,
+                queue_family.queue_count,
+                queue_family.timestamp_valid_bits,
+                if queue_family.present_support {
+                    ", present"
+                } else {
+                    ""
+                }
+            )?;
+        }
+
+        writeln!(out, "  Surface formats:")?;
+        for format in &device.surface_formats {
+            writeln!(out, "    {} {}", format.format, format.color_space)?;
+        }
+        writeln!(out, "  Present modes: {}", device.present_modes.join(", "))?;
+
+        writeln!(out, "  Extensions ({}):", device.extensions.len())?;
+        for extension in &device.extensions {
+            writeln!(out, "    {} ({})", extension.name, extension.spec_version)?;
+        }
+    }
+    Ok(())
+}
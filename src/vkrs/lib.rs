@@ -0,0 +1,65 @@
+//! A Vulkan renderer.
+//!
+//! [`Renderer`] draws meshes with materials, a camera and lights, either to any window that
+//! implements [`HasRawWindowHandle`](raw_window_handle::HasRawWindowHandle) or headless to an
+//! offscreen image that is read back. [`App`] is the winit window of the `vkrs` binary built on
+//! top of it.
+
+mod app;
+mod bench;
+mod camera;
+mod capabilities;
+mod clock;
+mod deletion_queue;
+mod device_features;
+mod device_selector;
+mod display;
+mod extensions;
+mod frame_context;
+mod frame_stats;
+mod gpu_profiler;
+mod gpu_timeline;
+mod handles;
+mod info;
+mod light;
+mod material;
+mod mesh;
+mod overlay;
+mod queue_family_indices;
+mod renderer;
+mod scene;
+mod shader;
+mod swapchain;
+// Shared with the tests of the binary.
+#[cfg(test)]
+#[path = "../test_util.rs"]
+mod test_util;
+mod tonemap;
+mod trace;
+mod uniform_buffer_object;
+mod upload;
+mod validation;
+mod validation_sink;
+mod vertex;
+mod vulkan;
+
+pub use app::{App, AppConfig, RedrawMode};
+pub use bench::BenchConfig;
+pub use camera::Camera;
+pub use device_selector::DeviceSelector;
+pub use display::{choose_fullscreen, MonitorSelector, VideoModeRequest, WindowMode};
+pub use frame_stats::FrameStatsSummary;
+pub use gpu_timeline::FrameSyncMode;
+pub use info::print_device_info;
+pub use light::{Light, MAX_LIGHTS};
+pub use material::{Material, MaterialHandle};
+pub use mesh::MeshHandle;
+pub use renderer::{Renderer, RendererConfig};
+pub use scene::Scene;
+pub use swapchain::{HdrMode, PresentModePreference, SwapchainPreferences};
+pub use validation::ValidationConfig;
+pub use validation_sink::{ValidationCollector, ValidationMessage, ValidationObject};
+pub use vertex::Vertex;
+
+/// The math types of the API.
+pub use glam;
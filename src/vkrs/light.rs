@@ -0,0 +1,55 @@
+//! The lights the scene is shaded with.
+
+use glam::{Vec3, Vec4};
+
+/// Maximum number of directional and point lights, which must match `MAX_LIGHTS` in
+/// `shader.frag`. Ambient lights do not count towards it.
+pub const MAX_LIGHTS: usize = 8;
+
+/// A light in linear BT.709, where a color of 1.0 lights a white surface facing it to paper white.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    /// Lights every surface evenly, regardless of its normal.
+    Ambient { color: Vec3 },
+    /// Infinitely far away, like the sun. `direction` is the way the light travels.
+    Directional { direction: Vec3, color: Vec3 },
+    /// Falls off with the square of the distance to `position`.
+    Point { position: Vec3, color: Vec3 },
+}
+
+/// A directional or point light as laid out in the uniform buffer.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub struct GpuLight {
+    /// The position of point lights with w = 1, or the direction of directional lights with
+    /// w = 0.
+    pub position: Vec4,
+    pub color: Vec4,
+}
+
+/// Sums the ambient lights and lays out the others for the uniform buffer. Panics if there are
+/// more than [`MAX_LIGHTS`] directional and point lights.
+pub fn pack_lights(lights: &[Light]) -> (Vec3, Vec<GpuLight>) {
+    let mut ambient = Vec3::ZERO;
+    let mut packed = Vec::new();
+    for light in lights {
+        match *light {
+            Light::Ambient { color } => ambient += color,
+            Light::Directional { direction, color } => packed.push(GpuLight {
+                position: direction.normalize().extend(0.0),
+                color: color.extend(0.0),
+            }),
+            Light::Point { position, color } => packed.push(GpuLight {
+                position: position.extend(1.0),
+                color: color.extend(0.0),
+            }),
+        }
+    }
+    assert!(
+        packed.len() <= MAX_LIGHTS,
+        "{} directional and point lights given, at most {} are supported",
+        packed.len(),
+        MAX_LIGHTS
+    );
+    (ambient, packed)
+}
@@ -0,0 +1,27 @@
+//! How meshes are shaded.
+
+use glam::{Vec3, Vec4};
+
+/// Handle to a material added with [`Renderer::add_material`](super::Renderer::add_material).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(pub(crate) usize);
+
+/// A diffuse material, in linear BT.709.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    /// Multiplied with the vertex colors. The alpha is written to the scene image as is, meshes
+    /// are not blended.
+    pub base_color: Vec4,
+    /// Added regardless of the lights.
+    pub emissive: Vec3,
+}
+
+impl Default for Material {
+    /// White, so that meshes are shaded with their vertex colors.
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            emissive: Vec3::ZERO,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+//! Meshes that have been uploaded to the GPU.
+
+use super::handles::Buffer;
+use super::upload::UploadHandle;
+
+/// Handle to a mesh added with [`Renderer::add_mesh`](super::Renderer::add_mesh).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle(pub(crate) usize);
+
+/// An indexed triangle list. Meshes are not drawn until their upload is ready.
+pub struct Mesh {
+    pub num_indices: u32,
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub upload: UploadHandle,
+}
@@ -0,0 +1,230 @@
+//! A graph of the most recent frame times, drawn in the top left corner of the scene.
+
+use super::frame_stats::DrawStats;
+use super::handles::{Device, Pipeline, PipelineLayout};
+use super::shader;
+
+use ash::vk;
+
+use std::{ffi::CString, rc::Rc, time::Duration};
+
+/// Number of frame times in the graph, each is drawn as a bar `BAR_WIDTH` pixels wide.
+const NUM_FRAME_TIMES: usize = 128;
+// In logical pixels, which are scaled by the scale factor of the window.
+const BAR_WIDTH: u32 = 2;
+const WIDTH: u32 = NUM_FRAME_TIMES as u32 * BAR_WIDTH;
+const HEIGHT: u32 = 96;
+const MARGIN: u32 = 16;
+
+/// The frame times in quarter milliseconds, one byte each, as read by `overlay.frag`.
+type PushConstants = [u8; NUM_FRAME_TIMES];
+
+pub struct OverlayPass {
+    pipeline: Pipeline,
+    pipeline_layout: PipelineLayout,
+}
+
+impl OverlayPass {
+    /// `scene_render_pass` is the pass the overlay is drawn in, at the end of the scene.
+    /// `scale_factor` is the ratio of physical to logical pixels of the window, so that the
+    /// overlay has the same size on screens of any DPI.
+    pub fn new(
+        device: &Rc<Device>,
+        scene_render_pass: vk::RenderPass,
+        scene_samples: vk::SampleCountFlags,
+        swapchain_extent: vk::Extent2D,
+        scale_factor: f64,
+    ) -> Self {
+        let (pipeline, pipeline_layout) = create_pipeline(
+            device,
+            swapchain_extent,
+            scale_factor,
+            scene_render_pass,
+            scene_samples,
+        );
+        pipeline.set_name("overlay pipeline");
+        pipeline_layout.set_name("overlay pipeline layout");
+        Self {
+            pipeline,
+            pipeline_layout,
+        }
+    }
+
+    /// Records the overlay into `command_buffer`, which must be inside the scene render pass.
+    /// `frame_times` are oldest first, only the most recent ones that fit are drawn.
+    pub fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        frame_times: impl ExactSizeIterator<Item = Duration>,
+    ) -> DrawStats {
+        let mut push_constants: PushConstants = [0; NUM_FRAME_TIMES];
+        let skip = frame_times.len().saturating_sub(NUM_FRAME_TIMES);
+        let offset = NUM_FRAME_TIMES - (frame_times.len() - skip);
+        for (sample, frame_time) in push_constants[offset..]
+            .iter_mut()
+            .zip(frame_times.skip(skip))
+        {
+            *sample = (frame_time.as_secs_f64() * 4000.0).round().min(255.0) as u8;
+        }
+
+        device.begin_label(command_buffer, "overlay");
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.handle(),
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout.handle(),
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                &push_constants,
+            );
+            // A single triangle covering the viewport, generated in the vertex shader.
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+        device.end_label(command_buffer);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            pipeline_binds: 1,
+            descriptor_binds: 0,
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Rc<Device>,
+    swapchain_extent: vk::Extent2D,
+    scale_factor: f64,
+    render_pass: vk::RenderPass,
+    samples: vk::SampleCountFlags,
+) -> (Pipeline, PipelineLayout) {
+    let vertex_shader_code = shader::read_spirv(include_bytes!("shaders/overlay.vert.spv"));
+    let fragment_shader_code = shader::read_spirv(include_bytes!("shaders/overlay.frag.spv"));
+
+    let vertex_shader_module = shader::create_shader_module(device, &vertex_shader_code);
+    let fragment_shader_module = shader::create_shader_module(device, &fragment_shader_code);
+    vertex_shader_module.set_name("overlay.vert");
+    fragment_shader_module.set_name("overlay.frag");
+
+    let shader_entry_point = CString::new("main").unwrap();
+    let vertex_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(vertex_shader_module.handle())
+        .name(&shader_entry_point)
+        .build();
+    let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(fragment_shader_module.handle())
+        .name(&shader_entry_point)
+        .build();
+
+    let shader_stages = [vertex_shader_stage_info, fragment_shader_stage_info];
+
+    // The vertices are generated from their index, there is no vertex input.
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    // The viewport is the overlay rectangle in physical pixels, clamped to the swapchain.
+    let physical = |logical: u32| (f64::from(logical) * scale_factor).round() as u32;
+    let margin = physical(MARGIN);
+    let width = physical(WIDTH).min(swapchain_extent.width.saturating_sub(margin));
+    let height = physical(HEIGHT).min(swapchain_extent.height.saturating_sub(margin));
+    let viewport = vk::Viewport::builder()
+        .x(margin as _)
+        .y(margin as _)
+        .width(width.max(1) as _)
+        .height(height.max(1) as _)
+        .min_depth(0.0)
+        .max_depth(1.0)
+        .build();
+    let viewports = [viewport];
+    let scissor = vk::Rect2D::builder()
+        .offset(vk::Offset2D {
+            x: margin as _,
+            y: margin as _,
+        })
+        .extent(vk::Extent2D { width, height })
+        .build();
+    let scissors = [scissor];
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewports(&viewports)
+        .scissors(&scissors);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(samples);
+
+    // Drawn on top of the scene regardless of its depth.
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(false)
+        .depth_write_enable(false)
+        .stencil_test_enable(false);
+
+    // Blended over the scene, which keeps its own alpha.
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::RGBA)
+        .blend_enable(true)
+        .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+        .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+        .color_blend_op(vk::BlendOp::ADD)
+        .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+        .dst_alpha_blend_factor(vk::BlendFactor::ONE)
+        .alpha_blend_op(vk::BlendOp::ADD)
+        .build();
+    let color_blend_attachments = [color_blend_attachment];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&color_blend_attachments);
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(NUM_FRAME_TIMES as _)
+        .build();
+    let push_constant_ranges = [push_constant_range];
+    let pipeline_layout_info =
+        vk::PipelineLayoutCreateInfo::builder().push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout = unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_info, None)
+            .unwrap()
+    };
+    let pipeline_layout = PipelineLayout::new(device, pipeline_layout);
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&color_blending)
+        .layout(pipeline_layout.handle())
+        .render_pass(render_pass)
+        .subpass(0)
+        .build();
+    let pipeline_infos = [pipeline_info];
+    let pipeline = unsafe {
+        device
+            .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+            .unwrap()[0]
+    };
+
+    (Pipeline::new(device, pipeline), pipeline_layout)
+}
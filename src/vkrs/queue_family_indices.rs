@@ -1,3 +1,5 @@
+use super::handles::Surface;
+
 use ash::vk;
 
 pub struct QueueFamilyIndices {
@@ -15,10 +17,10 @@ impl QueueFamilyIndices {
         }
     }
 
+    /// Without a surface nothing is presented, and the present family is the graphics family.
     pub fn find_queue_families(
         instance: &ash::Instance,
-        surface_fn: &ash::extensions::khr::Surface,
-        surface: vk::SurfaceKHR,
+        surface: Option<&Surface>,
         device: vk::PhysicalDevice,
     ) -> QueueFamilyIndices {
         let queue_families =
@@ -29,10 +31,14 @@ impl QueueFamilyIndices {
             if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
                 indices.graphics_family = Some(index);
             }
-            let has_present_support = unsafe {
-                surface_fn
-                    .get_physical_device_surface_support(device, index, surface)
-                    .unwrap()
+            let has_present_support = match surface {
+                Some(surface) => unsafe {
+                    surface
+                        .loader()
+                        .get_physical_device_surface_support(device, index, surface.handle())
+                        .unwrap()
+                },
+                None => indices.graphics_family == Some(index),
             };
             if has_present_support && indices.present_family.is_none() {
                 indices.present_family = Some(index)
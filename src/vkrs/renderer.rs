@@ -0,0 +1,1208 @@
+//! The renderer, which draws meshes to a window surface or, headless, to an offscreen image.
+
+use super::bench::{BenchSettings, Benchmark};
+use super::camera::Camera;
+use super::deletion_queue::DeletionQueue;
+use super::device_features::DeviceRequirements;
+use super::device_selector::DeviceSelector;
+use super::frame_context::FrameContext;
+use super::frame_stats::{FrameStats, FrameStatsSummary};
+use super::gpu_profiler::GpuProfiler;
+use super::gpu_timeline::{FrameSyncMode, GpuTimeline, Submission};
+use super::handles::{
+    Buffer, CommandPool, DebugMessenger, DescriptorPool, DescriptorSetLayout, Device, Image,
+    ImageView, Instance, Pipeline, PipelineLayout, RenderPass, Surface, Swapchain,
+};
+use super::light::{self, GpuLight, Light, MAX_LIGHTS};
+use super::material::{Material, MaterialHandle};
+use super::mesh::{Mesh, MeshHandle};
+use super::overlay::OverlayPass;
+use super::queue_family_indices::QueueFamilyIndices;
+use super::swapchain::{self, SwapchainPreferences};
+use super::tonemap::{self, TonemapPass};
+use super::trace;
+use super::uniform_buffer_object::{ObjectPushConstants, UniformBufferObject};
+use super::upload::UploadManager;
+use super::validation::ValidationConfig;
+use super::validation_sink::ValidationCollector;
+use super::vertex::Vertex;
+use super::vulkan::{self, SceneDraw};
+
+use ash::vk;
+use glam::{Mat4, Vec3};
+use raw_window_handle::HasRawWindowHandle;
+use std::{mem, rc::Rc};
+
+const VERSION_MAJOR: &str = env!("CARGO_PKG_VERSION_MAJOR");
+const VERSION_MINOR: &str = env!("CARGO_PKG_VERSION_MINOR");
+const VERSION_PATCH: &str = env!("CARGO_PKG_VERSION_PATCH");
+
+const DEFAULT_FRAMES_IN_FLIGHT: u32 = 2;
+
+/// Format of the offscreen image headless renderers render to, see [`Renderer::read_pixels`].
+const HEADLESS_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+/// What the renderer is created with.
+#[derive(Clone)]
+pub struct RendererConfig {
+    /// Forces a specific physical device, see [`DeviceSelector`].
+    pub device_selector: Option<DeviceSelector>,
+    /// Ignored by headless renderers.
+    pub swapchain_preferences: SwapchainPreferences,
+    /// Number of frames the CPU may record ahead of the GPU.
+    pub frames_in_flight: u32,
+    /// Samples per pixel of the scene, lowered to the highest count the device supports. 1
+    /// disables MSAA.
+    pub msaa_samples: u32,
+    /// How the completion of frames is tracked.
+    pub frame_sync: FrameSyncMode,
+    pub validation: ValidationConfig,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            device_selector: None,
+            swapchain_preferences: SwapchainPreferences::default(),
+            frames_in_flight: DEFAULT_FRAMES_IN_FLIGHT,
+            msaa_samples: 1,
+            frame_sync: FrameSyncMode::default(),
+            validation: ValidationConfig::default(),
+        }
+    }
+}
+
+/// What the frames are rendered to.
+enum Target {
+    Swapchain {
+        swapchain: Swapchain,
+        images: Vec<vk::Image>,
+    },
+    /// A single image, that every frame is copied from into `readback_buffer`.
+    Offscreen {
+        image: Image,
+        readback_buffer: Buffer,
+    },
+}
+
+impl Target {
+    fn num_images(&self) -> usize {
+        match self {
+            Self::Swapchain { images, .. } => images.len(),
+            Self::Offscreen { .. } => 1,
+        }
+    }
+}
+
+/// Renders meshes with a camera and lights, to a window or headless.
+///
+/// Every frame, queue the meshes to draw with [`Renderer::draw`] and render them with
+/// [`Renderer::render`]. Until [`Renderer::set_lights`] is called the scene is lit by a white
+/// ambient light only, so meshes with the default [`Material`] show their vertex colors.
+///
+/// Fields are dropped in declaration order, which destroys children before the parents they hold
+/// on to. Nothing may be destroyed while in use by the GPU, see `Drop for Renderer`.
+pub struct Renderer {
+    frame_stats: FrameStats,
+    show_overlay: bool,
+    // Ratio of physical to logical pixels of the window, which the overlay is laid out with.
+    scale_factor: f64,
+    // The size of the window, or of the offscreen image.
+    size: vk::Extent2D,
+    // Resizes and setting changes only mark the target for recreation, so that however many of
+    // them arrive between two frames it is recreated once, at the next frame.
+    target_outdated: bool,
+    // Set between the window being resized without a resize event, such as when switching to or
+    // from fullscreen, and the swapchain matching the window again.
+    surface_resize_pending: bool,
+    camera: Camera,
+    ambient_light: Vec3,
+    lights: Vec<GpuLight>,
+    materials: Vec<Material>,
+    // Cleared after every frame.
+    draws: Vec<(MeshHandle, MaterialHandle, Mat4)>,
+    frames: Vec<FrameContext>,
+    current_frame: usize,
+    // The requested number of frames in flight, there are never more frames than target images.
+    frames_in_flight: u32,
+    // GPU progress value of the last submission that rendered to each target image, so that an
+    // image is never rendered to by two frames in flight at once.
+    images_in_flight: Vec<u64>,
+    deletion_queue: DeletionQueue,
+    meshes: Vec<Mesh>,
+    upload_manager: UploadManager,
+    overlay_pass: OverlayPass,
+    tonemap_pass: TonemapPass,
+    graphics_pipeline: Pipeline,
+    pipeline_layout: PipelineLayout,
+    // Renders the scene to the HDR and depth images owned by the tonemap pass.
+    render_pass: RenderPass,
+    msaa_samples: vk::SampleCountFlags,
+    depth_format: vk::Format,
+    target_image_views: Vec<ImageView>,
+    target: Target,
+    target_extent: vk::Extent2D,
+    swapchain_preferences: SwapchainPreferences,
+    // The descriptor sets and command buffers of the frames are allocated from these pools.
+    descriptor_pool: DescriptorPool,
+    descriptor_set_layout: DescriptorSetLayout,
+    command_pool: CommandPool,
+    gpu_timeline: GpuTimeline,
+    gpu_profiler: Option<GpuProfiler>,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+    queue_family_indices: QueueFamilyIndices,
+    device: Rc<Device>,
+    physical_device: vk::PhysicalDevice,
+    // Headless renderers have no surface.
+    surface: Option<Rc<Surface>>,
+    _debug_messenger: DebugMessenger,
+    // Collects validation errors when the application should fail if any were emitted.
+    validation_collector: Option<ValidationCollector>,
+    _instance: Rc<Instance>,
+}
+
+impl Renderer {
+    /// Creates a renderer that presents to `window`, which is `size` physical pixels large and
+    /// has `scale_factor` physical pixels per logical pixel.
+    pub fn new(
+        name: &str,
+        window: &dyn HasRawWindowHandle,
+        size: [u32; 2],
+        scale_factor: f64,
+        config: &RendererConfig,
+    ) -> Self {
+        Self::create(name, Some(window), size, scale_factor, config)
+    }
+
+    /// Creates a renderer that renders to an offscreen image of `size` pixels, which is read back
+    /// with [`Renderer::read_pixels`]. Needs neither a window nor a device that can present.
+    pub fn headless(name: &str, size: [u32; 2], config: &RendererConfig) -> Self {
+        assert!(
+            size[0] > 0 && size[1] > 0,
+            "Headless renderers need a size of at least 1x1, got {}x{}",
+            size[0],
+            size[1]
+        );
+        Self::create(name, None, size, 1.0, config)
+    }
+
+    /// Every resource is owned as soon as it has been created, so if creation panics partway
+    /// the resources created so far are released while unwinding.
+    fn create(
+        name: &str,
+        window: Option<&dyn HasRawWindowHandle>,
+        size: [u32; 2],
+        scale_factor: f64,
+        config: &RendererConfig,
+    ) -> Self {
+        let (instance, debug_messenger, surface) =
+            Self::create_instance_and_surface(name, window, &config.validation);
+        let validation_collector = instance.validation().fail_on_error.then(|| {
+            let collector = ValidationCollector::default();
+            debug_messenger.add_sink(Box::new(collector.clone()));
+            collector
+        });
+        let device_requirements =
+            Self::device_requirements(config.frame_sync, instance.validation(), surface.is_some());
+        let (physical_device, queue_family_indices, capabilities) = vulkan::select_physical_device(
+            &instance,
+            surface.as_deref(),
+            config.device_selector.as_ref(),
+            &device_requirements,
+        );
+        let (device, graphics_queue, present_queue) =
+            vulkan::create_logical_device_with_graphics_and_present_queue(
+                &instance,
+                &queue_family_indices,
+                physical_device,
+                capabilities,
+            );
+        let size = vk::Extent2D {
+            width: size[0],
+            height: size[1],
+        };
+        let (target, target_format, target_extent, target_image_views) = create_target(
+            &device,
+            physical_device,
+            surface.as_ref(),
+            &queue_family_indices,
+            size,
+            config.swapchain_preferences,
+            None,
+        );
+
+        let msaa_samples = vulkan::choose_sample_count(
+            &instance,
+            physical_device,
+            tonemap::SCENE_FORMAT,
+            config.msaa_samples,
+        );
+        let depth_format = vulkan::choose_depth_format(&instance, physical_device);
+        let render_pass = vulkan::create_render_pass(
+            &device,
+            tonemap::SCENE_FORMAT,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            msaa_samples,
+            Some(depth_format),
+        );
+        render_pass.set_name("scene render pass");
+        let descriptor_set_layout = vulkan::create_descriptor_set_layout(&device);
+        let (graphics_pipeline, pipeline_layout) = vulkan::create_graphics_pipeline(
+            &device,
+            target_extent,
+            render_pass.handle(),
+            descriptor_set_layout.handle(),
+            msaa_samples,
+        );
+
+        let overlay_pass = OverlayPass::new(
+            &device,
+            render_pass.handle(),
+            msaa_samples,
+            target_extent,
+            scale_factor,
+        );
+
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let tonemap_pass = TonemapPass::new(
+            &device,
+            memory_properties,
+            render_pass.handle(),
+            msaa_samples,
+            depth_format,
+            target_format,
+            &target_image_views,
+            final_layout(&target),
+            target_extent,
+        );
+
+        let transient_command_pool = vulkan::create_command_pool(
+            &device,
+            vk::CommandPoolCreateFlags::TRANSIENT,
+            &queue_family_indices,
+        );
+        transient_command_pool.set_name("upload command pool");
+        let upload_manager = UploadManager::new(&device, memory_properties, transient_command_pool);
+
+        let gpu_profiler = GpuProfiler::new(
+            &device,
+            physical_device,
+            queue_family_indices.graphics_family.unwrap(),
+        );
+        let (frames, descriptor_pool, command_pool) = Self::create_frames(
+            &device,
+            memory_properties,
+            &queue_family_indices,
+            descriptor_set_layout.handle(),
+            gpu_profiler.as_ref(),
+            Self::num_frames(config.frames_in_flight, target.num_images()),
+        );
+        let images_in_flight = vec![0; target.num_images()];
+        let gpu_timeline = GpuTimeline::new(&device, config.frame_sync);
+
+        Self {
+            frame_stats: FrameStats::new(),
+            show_overlay: false,
+            scale_factor,
+            size,
+            target_outdated: false,
+            surface_resize_pending: false,
+            camera: Camera::default(),
+            ambient_light: Vec3::ONE,
+            lights: Vec::new(),
+            materials: Vec::new(),
+            draws: Vec::new(),
+            frames,
+            current_frame: 0,
+            frames_in_flight: config.frames_in_flight,
+            images_in_flight,
+            deletion_queue: DeletionQueue::new(),
+            meshes: Vec::new(),
+            upload_manager,
+            overlay_pass,
+            tonemap_pass,
+            graphics_pipeline,
+            pipeline_layout,
+            render_pass,
+            msaa_samples,
+            depth_format,
+            target_image_views,
+            target,
+            target_extent,
+            swapchain_preferences: config.swapchain_preferences,
+            descriptor_pool,
+            descriptor_set_layout,
+            command_pool,
+            gpu_timeline,
+            gpu_profiler,
+            graphics_queue,
+            present_queue,
+            queue_family_indices,
+            device,
+            physical_device,
+            surface,
+            _debug_messenger: debug_messenger,
+            validation_collector,
+            _instance: instance,
+        }
+    }
+
+    /// More frames in flight than target images would only wait for the images to be released.
+    fn num_frames(frames_in_flight: u32, num_target_images: usize) -> u32 {
+        frames_in_flight.min(num_target_images as u32)
+    }
+
+    /// Creates the frame contexts together with the pools their command buffers and descriptor
+    /// sets are allocated from, so that they can be recreated together.
+    fn create_frames(
+        device: &Rc<Device>,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        queue_family_indices: &QueueFamilyIndices,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        gpu_profiler: Option<&GpuProfiler>,
+        num_frames: u32,
+    ) -> (Vec<FrameContext>, DescriptorPool, CommandPool) {
+        log::info!(target: "vkrs", "Using {} frames in flight", num_frames);
+        let command_pool = vulkan::create_command_pool(
+            device,
+            vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER,
+            queue_family_indices,
+        );
+        command_pool.set_name("frame command pool");
+        let descriptor_pool = vulkan::create_descriptor_pool(device, num_frames);
+        descriptor_pool.set_name("frame descriptor pool");
+        let frames = FrameContext::create_frame_contexts(
+            device,
+            memory_properties,
+            command_pool.handle(),
+            descriptor_pool.handle(),
+            descriptor_set_layout,
+            gpu_profiler,
+            num_frames,
+        );
+        (frames, descriptor_pool, command_pool)
+    }
+
+    /// Recreates the frame contexts if the number of frames in flight has changed, either because
+    /// it was changed or because the number of target images has.
+    fn recreate_frames(&mut self) {
+        let num_frames = Self::num_frames(self.frames_in_flight, self.target.num_images());
+        if num_frames as usize == self.frames.len() {
+            return;
+        }
+
+        let memory_properties = unsafe {
+            self.device
+                .instance()
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        let (frames, descriptor_pool, command_pool) = Self::create_frames(
+            &self.device,
+            memory_properties,
+            &self.queue_family_indices,
+            self.descriptor_set_layout.handle(),
+            self.gpu_profiler.as_ref(),
+            num_frames,
+        );
+
+        // The render finished semaphores may still be waited on by pending presents, so like the
+        // old swapchain they are kept around until the frame after the last one has finished.
+        let last_use = self.gpu_timeline.last_submitted_value();
+        self.deletion_queue.push(
+            last_use + 1,
+            (
+                mem::replace(&mut self.frames, frames),
+                mem::replace(&mut self.descriptor_pool, descriptor_pool),
+                mem::replace(&mut self.command_pool, command_pool),
+            ),
+        );
+        self.current_frame = 0;
+    }
+
+    /// Without a window, the instance is created without surface extensions and there is no
+    /// surface.
+    pub(crate) fn create_instance_and_surface(
+        name: &str,
+        window: Option<&dyn HasRawWindowHandle>,
+        validation: &ValidationConfig,
+    ) -> (Rc<Instance>, DebugMessenger, Option<Rc<Surface>>) {
+        let version_major = VERSION_MAJOR.parse().unwrap();
+        let version_minor = VERSION_MINOR.parse().unwrap();
+        let version_patch = VERSION_PATCH.parse().unwrap();
+
+        let entry = unsafe { ash::Entry::load().expect("Failed to load Vulkan.") };
+
+        let version = vk::make_api_version(0, version_major, version_minor, version_patch);
+
+        let instance = vulkan::create_instance(name, version, entry, window, validation);
+        let debug_messenger = vulkan::setup_debug_messenger(&instance);
+        let surface = window.map(|window| {
+            let surface_fn = ash::extensions::khr::Surface::new(instance.entry(), &instance);
+            let surface = unsafe {
+                // ash-window takes the raw-window-handle 0.3 trait, which the reference implements
+                // through the 0.4 trait.
+                ash_window::create_surface(instance.entry(), &instance, &window, None)
+                    .expect("Failed to create surface")
+            };
+            Surface::new(&instance, surface_fn, surface)
+        });
+        (instance, debug_messenger, surface)
+    }
+
+    /// Everything the renderer needs from a device, devices are selected and rated against this.
+    /// Only devices that `present` to a surface need swapchains.
+    pub(crate) fn device_requirements(
+        frame_sync_mode: FrameSyncMode,
+        validation: &ValidationConfig,
+        present: bool,
+    ) -> DeviceRequirements {
+        let mut device_requirements = DeviceRequirements::new(present);
+        GpuTimeline::request_features(frame_sync_mode, &mut device_requirements);
+        // Shaders that call `debugPrintfEXT` need it before Vulkan 1.3.
+        if validation.debug_printf {
+            device_requirements
+                .extensions
+                .request(vk::KhrShaderNonSemanticInfoFn::name());
+        }
+        device_requirements
+    }
+
+    /// Recreates the target at the current size, together with everything that depends on its
+    /// extent or format.
+    fn recreate_target(&mut self) {
+        let (target, target_format, target_extent, target_image_views) = create_target(
+            &self.device,
+            self.physical_device,
+            self.surface.as_ref(),
+            &self.queue_family_indices,
+            self.size,
+            self.swapchain_preferences,
+            Some(&self.target),
+        );
+        let (graphics_pipeline, pipeline_layout) = vulkan::create_graphics_pipeline(
+            &self.device,
+            target_extent,
+            self.render_pass.handle(),
+            self.descriptor_set_layout.handle(),
+            self.msaa_samples,
+        );
+        let overlay_pass = OverlayPass::new(
+            &self.device,
+            self.render_pass.handle(),
+            self.msaa_samples,
+            target_extent,
+            self.scale_factor,
+        );
+        let memory_properties = unsafe {
+            self.device
+                .instance()
+                .get_physical_device_memory_properties(self.physical_device)
+        };
+        let tonemap_pass = TonemapPass::new(
+            &self.device,
+            memory_properties,
+            self.render_pass.handle(),
+            self.msaa_samples,
+            self.depth_format,
+            target_format,
+            &target_image_views,
+            final_layout(&target),
+            target_extent,
+        );
+
+        // The old resources are released once the frames that used them have finished, so
+        // there is no need to wait for the device to become idle.
+        let last_use = self.gpu_timeline.last_submitted_value();
+        let queue = &mut self.deletion_queue;
+        queue.push(last_use, mem::replace(&mut self.tonemap_pass, tonemap_pass));
+        queue.push(last_use, mem::replace(&mut self.overlay_pass, overlay_pass));
+        queue.push(
+            last_use,
+            mem::replace(&mut self.graphics_pipeline, graphics_pipeline),
+        );
+        queue.push(
+            last_use,
+            mem::replace(&mut self.pipeline_layout, pipeline_layout),
+        );
+        queue.push(
+            last_use,
+            mem::replace(&mut self.target_image_views, target_image_views),
+        );
+        // An old swapchain may still have a pending present that waits for the last submitted
+        // frame, so it is kept around until the frame after it has finished as well.
+        queue.push(last_use + 1, mem::replace(&mut self.target, target));
+
+        self.target_extent = target_extent;
+        self.images_in_flight = vec![0; self.target.num_images()];
+        self.target_outdated = false;
+        self.recreate_frames();
+    }
+
+    /// Uploads a mesh, which is drawn with [`Renderer::draw`]. The upload is submitted with the
+    /// next frame, ahead of that frame's draws, so the mesh can be drawn right away. Meshes live
+    /// as long as the renderer. Panics if `vertices` or `indices` is empty.
+    pub fn add_mesh(&mut self, name: &str, vertices: &[Vertex], indices: &[u16]) -> MeshHandle {
+        // Vulkan does not allow buffers of size 0.
+        assert!(
+            !vertices.is_empty() && !indices.is_empty(),
+            "Mesh {} needs vertices and indices, got {} vertices and {} indices",
+            name,
+            vertices.len(),
+            indices.len()
+        );
+        let (vertex_buffer, _) = self.upload_manager.upload_buffer(
+            &format!("mesh:{} vertex buffer", name),
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vertices,
+        );
+        // TODO(lovew): Instead of allocating a separate buffer for vertex indices we should have
+        // allocated only a single buffer and simply used an offset into it to store vertex indices
+        // in the same memory after the vertices themselves.
+        // Both copies are recorded into the same batch, so the index buffer handle covers both.
+        let (index_buffer, upload) = self.upload_manager.upload_buffer(
+            &format!("mesh:{} index buffer", name),
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            indices,
+        );
+        self.meshes.push(Mesh {
+            num_indices: indices.len() as u32,
+            vertex_buffer,
+            index_buffer,
+            upload,
+        });
+        MeshHandle(self.meshes.len() - 1)
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        self.materials.push(material);
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    /// Changes a material, starting with the next frame.
+    pub fn set_material(&mut self, handle: MaterialHandle, material: Material) {
+        self.materials[handle.0] = material;
+    }
+
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+    }
+
+    /// Replaces the lights. Panics if there are more than [`MAX_LIGHTS`] directional and point
+    /// lights.
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        let (ambient_light, lights) = light::pack_lights(lights);
+        self.ambient_light = ambient_light;
+        self.lights = lights;
+    }
+
+    /// Queues `mesh` to be drawn with `material` and the model matrix `transform` in the next
+    /// frame.
+    pub fn draw(&mut self, mesh: MeshHandle, material: MaterialHandle, transform: Mat4) {
+        self.draws.push((mesh, material, transform));
+    }
+
+    /// The window or the offscreen image now is `size` physical pixels large, the target is
+    /// recreated at the next frame if that is a new size.
+    pub fn resize(&mut self, size: [u32; 2]) {
+        let size = vk::Extent2D {
+            width: size[0],
+            height: size[1],
+        };
+        if size != self.size {
+            self.size = size;
+            self.target_outdated = true;
+        }
+    }
+
+    /// The window is being resized without necessarily getting a resize event, such as when it
+    /// switches to or from fullscreen. The surface is polled until the swapchain matches the size
+    /// of the window again, see [`Renderer::is_resizing`].
+    pub fn expect_resize(&mut self) {
+        if self.surface.is_some() {
+            self.surface_resize_pending = true;
+            self.target_outdated = true;
+        }
+    }
+
+    /// Whether the surface is being polled for a new size, during which frames should be
+    /// rendered continuously so that the swapchain is recreated as soon as it has one.
+    pub fn is_resizing(&self) -> bool {
+        self.surface_resize_pending
+    }
+
+    /// The overlay is laid out in logical pixels, which are `scale_factor` physical pixels.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.target_outdated = true;
+    }
+
+    pub fn swapchain_preferences(&self) -> SwapchainPreferences {
+        self.swapchain_preferences
+    }
+
+    /// The swapchain is recreated with the new preferences at the next frame.
+    pub fn set_swapchain_preferences(&mut self, preferences: SwapchainPreferences) {
+        self.swapchain_preferences = preferences;
+        self.target_outdated = true;
+    }
+
+    /// Number of swapchain images, or 1 for headless renderers.
+    pub fn image_count(&self) -> usize {
+        self.target.num_images()
+    }
+
+    /// The requested number of frames in flight, the renderer never uses more than
+    /// [`Renderer::image_count`].
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight
+    }
+
+    pub fn set_frames_in_flight(&mut self, frames_in_flight: u32) {
+        self.frames_in_flight = frames_in_flight;
+        self.recreate_frames();
+    }
+
+    pub fn is_overlay_visible(&self) -> bool {
+        self.show_overlay
+    }
+
+    /// Shows or hides the graph of recent frame times, drawn over the scene.
+    pub fn set_overlay_visible(&mut self, visible: bool) {
+        self.show_overlay = visible;
+    }
+
+    /// Frame times, GPU waits and draw counts over the most recent frames.
+    pub fn frame_stats(&self) -> FrameStatsSummary {
+        self.frame_stats.summary()
+    }
+
+    /// Minimized windows have no area to render to. Some platforms report that as a zero window
+    /// size, and others only as a zero surface extent.
+    pub fn is_minimized(&self) -> bool {
+        let is_empty = |width, height| width == 0 || height == 0;
+        is_empty(self.size.width, self.size.height)
+            || self.surface.as_ref().is_some_and(|surface| {
+                swapchain::surface_extent(self.physical_device, surface)
+                    .is_some_and(|extent| is_empty(extent.width, extent.height))
+            })
+    }
+
+    /// Whether a frame should be rendered even if nothing else changed, because the target is
+    /// out of date. Polls the surface while the window is resizing, see
+    /// [`Renderer::expect_resize`].
+    pub fn needs_redraw(&mut self) -> bool {
+        if self.surface_resize_pending {
+            self.poll_surface_resize();
+        }
+        self.target_outdated
+    }
+
+    /// Surfaces that dictate the swapchain extent may only report the new size of the window some
+    /// frames after it was resized, and presenting to a swapchain of the old size does not
+    /// necessarily fail in between, so the surface is polled until the swapchain matches the
+    /// window.
+    fn poll_surface_resize(&mut self) {
+        if self.target_extent == self.size {
+            self.surface_resize_pending = false;
+            return;
+        }
+        let surface_extent = self
+            .surface
+            .as_ref()
+            .and_then(|surface| swapchain::surface_extent(self.physical_device, surface));
+        if surface_extent.is_some_and(|extent| extent != self.target_extent) {
+            self.target_outdated = true;
+        }
+    }
+
+    /// Renders the draws queued since the last frame, and clears the queue. Returns whether a
+    /// frame was rendered, which it is not while the window is minimized or when the swapchain
+    /// turned out to be out of date.
+    pub fn render(&mut self) -> bool {
+        if self.is_minimized() {
+            self.draws.clear();
+            return false;
+        }
+        if self.target_outdated {
+            self.recreate_target();
+        }
+        let rendered = self.draw_frame();
+        self.draws.clear();
+        rendered
+    }
+
+    fn update_uniform_buffer(&self) {
+        let _span = trace::span("update_uniform_buffer");
+        let aspect_ratio = self.target_extent.width as f32 / self.target_extent.height as f32;
+        let mut lights = [GpuLight::default(); MAX_LIGHTS];
+        lights[..self.lights.len()].copy_from_slice(&self.lights);
+        let ubo = UniformBufferObject {
+            view: self.camera.view(),
+            proj: self.camera.projection(aspect_ratio),
+            ambient: self.ambient_light.extend(0.0),
+            light_count: [self.lights.len() as u32, 0, 0, 0],
+            lights,
+        };
+        self.frames[self.current_frame].write_uniform_buffer(ubo);
+    }
+
+    fn draw_frame(&mut self) -> bool {
+        let _span = trace::span("draw_frame");
+        self.frame_stats.begin_frame();
+        let frame = &self.frames[self.current_frame];
+        self.gpu_timeline.wait(frame.timeline_value);
+
+        let image_index = match &self.target {
+            Target::Swapchain { swapchain, .. } => {
+                let acquire_span = trace::span("acquire");
+                let result = unsafe {
+                    swapchain.loader().acquire_next_image(
+                        swapchain.handle(),
+                        u64::MAX,
+                        frame.image_available_semaphore.handle(),
+                        vk::Fence::null(),
+                    )
+                };
+                drop(acquire_span);
+                match result {
+                    Ok((image_index, _)) => image_index,
+                    Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                        self.target_outdated = true;
+                        return false;
+                    }
+                    Err(error) => panic!("Error acquiring next image: {:?}", error),
+                }
+            }
+            Target::Offscreen { .. } => 0,
+        };
+
+        // The image may still be rendered to by another frame in flight if the swapchain hands
+        // out images in a different order than the frames are submitted in, and the offscreen
+        // image is rendered to by every frame.
+        self.gpu_timeline
+            .wait(self.images_in_flight[image_index as usize]);
+
+        // The frame's previous submission has finished, so its timestamps are available.
+        if let (Some(gpu_profiler), Some(timestamps)) = (
+            self.gpu_profiler.as_mut(),
+            self.frames[self.current_frame].timestamps.as_mut(),
+        ) {
+            gpu_profiler.collect(timestamps);
+        }
+
+        self.update_uniform_buffer();
+        self.upload_manager.poll(&mut self.gpu_timeline);
+        let completed_value = self.gpu_timeline.completed_value();
+        self.deletion_queue.collect(completed_value);
+
+        // Submitted ahead of the frame on the same queue, the upload batch's barrier makes the
+        // copies visible to this frame's draws, so meshes added since the last frame are drawn.
+        self.upload_manager
+            .submit(self.graphics_queue, &mut self.gpu_timeline);
+        let draws = self
+            .draws
+            .iter()
+            .map(|(mesh, material, transform)| (&self.meshes[mesh.0], material, transform))
+            .filter(|(mesh, _, _)| self.upload_manager.is_ready(mesh.upload))
+            .map(|(mesh, material, transform)| {
+                let material = &self.materials[material.0];
+                SceneDraw {
+                    vertex_buffer: mesh.vertex_buffer.handle(),
+                    index_buffer: mesh.index_buffer.handle(),
+                    num_indices: mesh.num_indices,
+                    push_constants: ObjectPushConstants {
+                        model: *transform,
+                        base_color: material.base_color,
+                        emissive: material.emissive.extend(0.0),
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let frame = &mut self.frames[self.current_frame];
+        let command_buffer = frame.command_buffer;
+        let descriptor_set = frame.descriptor_set;
+        let record_span = trace::span("record_command_buffer");
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())
+                .unwrap()
+        };
+        let begin_info = vk::CommandBufferBeginInfo::builder();
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)
+                .unwrap()
+        };
+        let mut timestamps = frame.timestamps.as_mut();
+        if let Some(timestamps) = timestamps.as_mut() {
+            timestamps.begin_frame(&self.device, command_buffer);
+            timestamps.begin_scope(&self.device, command_buffer, "frame");
+            timestamps.begin_scope(&self.device, command_buffer, "scene pass");
+        }
+        self.device.begin_label(command_buffer, "scene pass");
+        vulkan::begin_render_pass(
+            &self.device,
+            command_buffer,
+            self.render_pass.handle(),
+            self.tonemap_pass.scene_framebuffer(),
+            self.target_extent,
+        );
+        let mut draw_stats = vulkan::record_scene_pass(
+            &self.device,
+            command_buffer,
+            self.graphics_pipeline.handle(),
+            &draws,
+            self.pipeline_layout.handle(),
+            descriptor_set,
+        );
+        // Drawn into the scene, so that it is tonemapped and encoded like everything else.
+        if self.show_overlay {
+            if let Some(timestamps) = timestamps.as_mut() {
+                timestamps.begin_scope(&self.device, command_buffer, "overlay");
+            }
+            draw_stats += self.overlay_pass.record(
+                &self.device,
+                command_buffer,
+                self.frame_stats.frame_times(),
+            );
+            if let Some(timestamps) = timestamps.as_mut() {
+                timestamps.end_scope(&self.device, command_buffer);
+            }
+        }
+        unsafe { self.device.cmd_end_render_pass(command_buffer) };
+        self.device.end_label(command_buffer);
+        if let Some(timestamps) = timestamps.as_mut() {
+            timestamps.end_scope(&self.device, command_buffer);
+            timestamps.begin_scope(&self.device, command_buffer, "tonemap pass");
+        }
+        draw_stats += self.tonemap_pass.record(
+            &self.device,
+            command_buffer,
+            image_index,
+            self.target_extent,
+        );
+        if let Some(timestamps) = timestamps.as_mut() {
+            timestamps.end_scope(&self.device, command_buffer);
+        }
+        if let Target::Offscreen {
+            image,
+            readback_buffer,
+        } = &self.target
+        {
+            record_readback(
+                &self.device,
+                command_buffer,
+                image.handle(),
+                readback_buffer.handle(),
+                self.target_extent,
+            );
+        }
+        if let Some(timestamps) = timestamps {
+            timestamps.end_scope(&self.device, command_buffer);
+        }
+        unsafe { self.device.end_command_buffer(command_buffer).unwrap() };
+        drop(record_span);
+
+        // Offscreen frames neither wait for an image to be acquired nor signal a present.
+        let presents = matches!(self.target, Target::Swapchain { .. });
+        let wait_semaphores = [frame.image_available_semaphore.handle()];
+        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let command_buffers = [command_buffer];
+        let signal_semaphores = [frame.render_finished_semaphore.handle()];
+        let submission = if presents {
+            Submission {
+                command_buffers: &command_buffers,
+                wait_semaphores: &wait_semaphores,
+                wait_stages: &wait_stages,
+                signal_semaphores: &signal_semaphores,
+                ..Default::default()
+            }
+        } else {
+            Submission {
+                command_buffers: &command_buffers,
+                ..Default::default()
+            }
+        };
+        let submit_span = trace::span("submit");
+        let timeline_value = self.gpu_timeline.submit(self.graphics_queue, &submission);
+        drop(submit_span);
+        self.frames[self.current_frame].timeline_value = timeline_value;
+        self.images_in_flight[image_index as usize] = timeline_value;
+        self.frame_stats
+            .end_frame(self.gpu_timeline.take_wait_time(), draw_stats);
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
+
+        if let Target::Swapchain { swapchain, .. } = &self.target {
+            let swapchains = [swapchain.handle()];
+            let image_indices = [image_index];
+            let present_info = vk::PresentInfoKHR::builder()
+                .wait_semaphores(&signal_semaphores)
+                .swapchains(&swapchains)
+                .image_indices(&image_indices);
+            let present_span = trace::span("present");
+            let result = unsafe {
+                swapchain
+                    .loader()
+                    .queue_present(self.present_queue, &present_info)
+            };
+            drop(present_span);
+            match result {
+                Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.target_outdated = true,
+                Err(error) => panic!("Failed to present swapchain image: {:?}", error),
+                _ => {}
+            }
+        }
+        true
+    }
+
+    /// The last rendered frame of a headless renderer, as rows of sRGB encoded RGBA8 pixels from
+    /// the top, or `None` for renderers that present to a window. Waits for the frame to finish
+    /// rendering.
+    pub fn read_pixels(&mut self) -> Option<Vec<u8>> {
+        let readback_buffer = match &self.target {
+            Target::Offscreen {
+                readback_buffer, ..
+            } => readback_buffer,
+            Target::Swapchain { .. } => return None,
+        };
+        let last_submitted_value = self.gpu_timeline.last_submitted_value();
+        self.gpu_timeline.wait(last_submitted_value);
+
+        let size = self.target_extent.width as usize * self.target_extent.height as usize * 4;
+        let mut pixels = vec![0; size];
+        unsafe {
+            let data_ptr = self
+                .device
+                .map_memory(
+                    readback_buffer.memory(),
+                    0,
+                    size as vk::DeviceSize,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .expect("Failed to map readback buffer memory.");
+            pixels.copy_from_slice(std::slice::from_raw_parts(data_ptr as *const u8, size));
+            self.device.unmap_memory(readback_buffer.memory());
+        }
+        Some(pixels)
+    }
+
+    /// Records the GPU timings of the frame the benchmark just rendered, if there are any.
+    pub(crate) fn record_benchmark_frame(&self, benchmark: &mut Benchmark) {
+        match &self.gpu_profiler {
+            Some(profiler) => {
+                benchmark.record_frame(profiler.collected_frames(), profiler.latest())
+            }
+            None => benchmark.record_frame(0, &[]),
+        }
+    }
+
+    pub(crate) fn bench_report(&self, benchmark: &Benchmark, scene: &str) -> String {
+        benchmark.report(&BenchSettings {
+            instance: self.device.instance(),
+            physical_device: self.physical_device,
+            scene,
+            extent: self.target_extent,
+            frames_in_flight: self.frames.len(),
+            swapchain_images: self.target.num_images(),
+            memory: self.device.memory_usage(),
+        })
+    }
+
+    /// The collector of the validation errors emitted so far, if the renderer was created with
+    /// [`ValidationConfig::fail_on_error`]. It keeps collecting while the renderer is dropped.
+    pub fn validation_collector(&self) -> Option<ValidationCollector> {
+        self.validation_collector.clone()
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        unsafe { self.device.device_wait_idle().unwrap() };
+    }
+}
+
+/// Creates the swapchain for `surface`, or the offscreen image of headless renderers, together
+/// with views of its images. Returns the format and extent of the images.
+fn create_target(
+    device: &Rc<Device>,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<&Rc<Surface>>,
+    queue_family_indices: &QueueFamilyIndices,
+    size: vk::Extent2D,
+    preferences: SwapchainPreferences,
+    old_target: Option<&Target>,
+) -> (Target, vk::SurfaceFormatKHR, vk::Extent2D, Vec<ImageView>) {
+    let surface = match surface {
+        Some(surface) => surface,
+        None => return create_offscreen_target(device, physical_device, size),
+    };
+    let old_swapchain = match old_target {
+        Some(Target::Swapchain { swapchain, .. }) => Some(swapchain),
+        _ => None,
+    };
+    let (swapchain, surface_format, extent, images) = swapchain::create_swapchain_and_images(
+        physical_device,
+        device,
+        surface,
+        queue_family_indices,
+        size,
+        preferences,
+        old_swapchain,
+    );
+    let image_views = swapchain::create_image_views(device, &images, surface_format.format);
+    for (index, view) in image_views.iter().enumerate() {
+        view.set_name(&format!("swapchain image view {}", index));
+    }
+    (
+        Target::Swapchain { swapchain, images },
+        surface_format,
+        extent,
+        image_views,
+    )
+}
+
+fn create_offscreen_target(
+    device: &Rc<Device>,
+    physical_device: vk::PhysicalDevice,
+    size: vk::Extent2D,
+) -> (Target, vk::SurfaceFormatKHR, vk::Extent2D, Vec<ImageView>) {
+    log::info!(target: "vkrs",
+               "Rendering headless to a {}x{} {:?} image",
+               size.width,
+               size.height,
+               HEADLESS_FORMAT);
+    let memory_properties = unsafe {
+        device
+            .instance()
+            .get_physical_device_memory_properties(physical_device)
+    };
+    let image = vulkan::create_image(
+        device,
+        memory_properties,
+        size,
+        HEADLESS_FORMAT,
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        vk::SampleCountFlags::TYPE_1,
+    );
+    let readback_buffer = vulkan::create_buffer(
+        device,
+        memory_properties,
+        size.width as vk::DeviceSize * size.height as vk::DeviceSize * 4,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    let image_views = swapchain::create_image_views(device, &[image.handle()], HEADLESS_FORMAT);
+    image.set_name("offscreen image");
+    readback_buffer.set_name("readback buffer");
+    image_views[0].set_name("offscreen image view");
+    let format = vk::SurfaceFormatKHR {
+        format: HEADLESS_FORMAT,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    };
+    (
+        Target::Offscreen {
+            image,
+            readback_buffer,
+        },
+        format,
+        size,
+        image_views,
+    )
+}
+
+/// The layout the tonemap pass leaves the target images in.
+fn final_layout(target: &Target) -> vk::ImageLayout {
+    match target {
+        Target::Swapchain { .. } => vk::ImageLayout::PRESENT_SRC_KHR,
+        Target::Offscreen { .. } => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+    }
+}
+
+/// Copies the offscreen `image`, which the tonemap pass left in `TRANSFER_SRC_OPTIMAL`, into the
+/// host visible `buffer`.
+fn record_readback(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    buffer: vk::Buffer,
+    extent: vk::Extent2D,
+) {
+    // Chains onto the dependency at the end of the tonemap pass, which covers its layout
+    // transition.
+    let image_barrier = vk::ImageMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .build();
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+        .image_extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        })
+        .build();
+    // Makes the copy visible to `Renderer::read_pixels` once the frame has finished.
+    let buffer_barrier = vk::BufferMemoryBarrier::builder()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(buffer)
+        .offset(0)
+        .size(vk::WHOLE_SIZE)
+        .build();
+    device.begin_label(command_buffer, "readback");
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[image_barrier],
+        );
+        device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            buffer,
+            &[region],
+        );
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[buffer_barrier],
+            &[],
+        );
+    }
+    device.end_label(command_buffer);
+}
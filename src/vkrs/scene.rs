@@ -0,0 +1,215 @@
+//! The geometry that is rendered, either the built-in quad or a mesh loaded from a JSON file.
+
+use super::vertex::Vertex;
+
+use glam::{const_vec3, Vec2, Vec3};
+use serde::Deserialize;
+
+use std::{fs, path::Path};
+
+const QUAD_VERTICES: [Vertex; 4] = [
+    Vertex {
+        pos: const_vec3!([-0.5, -0.5, 0.0]),
+        normal: const_vec3!([0.0, 0.0, 1.0]),
+        color: const_vec3!([1.0, 0.0, 0.0]),
+    },
+    Vertex {
+        pos: const_vec3!([0.5, -0.5, 0.0]),
+        normal: const_vec3!([0.0, 0.0, 1.0]),
+        color: const_vec3!([0.0, 1.0, 0.0]),
+    },
+    Vertex {
+        pos: const_vec3!([0.5, 0.5, 0.0]),
+        normal: const_vec3!([0.0, 0.0, 1.0]),
+        color: const_vec3!([0.0, 0.0, 1.0]),
+    },
+    Vertex {
+        pos: const_vec3!([-0.5, 0.5, 0.0]),
+        normal: const_vec3!([0.0, 0.0, 1.0]),
+        color: const_vec3!([1.0, 1.0, 1.0]),
+    },
+];
+
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
+
+/// A scene file, for example
+///
+/// ```json
+/// {
+///     "vertices": [
+///         { "pos": [-0.5, -0.5], "color": [1.0, 0.0, 0.0] },
+///         { "pos": [0.5, -0.5], "color": [0.0, 1.0, 0.0] },
+///         { "pos": [0.0, 0.5], "color": [0.0, 0.0, 1.0] }
+///     ],
+///     "indices": [0, 1, 2]
+/// }
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    vertices: Vec<SceneVertex>,
+    indices: Vec<u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneVertex {
+    pos: [f32; 2],
+    color: [f32; 3],
+}
+
+/// An indexed triangle list in the plane z = 0, facing +z.
+#[derive(Clone)]
+pub struct Scene {
+    /// `quad` for the built-in scene, otherwise the path it was loaded from.
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+impl Scene {
+    /// A quad with a different color in each corner.
+    pub fn quad() -> Self {
+        Self {
+            name: "quad".to_owned(),
+            vertices: QUAD_VERTICES.to_vec(),
+            indices: QUAD_INDICES.to_vec(),
+        }
+    }
+
+    /// Loads a scene file, see `SceneFile` for the format.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read scene {}: {}", path.display(), error))?;
+        let file: SceneFile = serde_json::from_str(&contents)
+            .map_err(|error| format!("Failed to parse scene {}: {}", path.display(), error))?;
+
+        if file.indices.is_empty() || file.indices.len() % 3 != 0 {
+            return Err(format!(
+                "Scene {} has {} indices, expected a positive multiple of 3",
+                path.display(),
+                file.indices.len()
+            ));
+        }
+        if let Some(index) = file
+            .indices
+            .iter()
+            .find(|index| **index as usize >= file.vertices.len())
+        {
+            return Err(format!(
+                "Scene {} has index {} but only {} vertices",
+                path.display(),
+                index,
+                file.vertices.len()
+            ));
+        }
+
+        Ok(Self {
+            name: path.display().to_string(),
+            vertices: file
+                .vertices
+                .iter()
+                .map(|vertex| Vertex {
+                    pos: Vec2::from(vertex.pos).extend(0.0),
+                    normal: Vec3::Z,
+                    color: Vec3::from(vertex.color),
+                })
+                .collect(),
+            indices: file.indices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempFile;
+
+    const VERTICES: &str = r#"[
+        { "pos": [-0.5, -0.5], "color": [1.0, 0.0, 0.0] },
+        { "pos": [0.5, -0.5], "color": [0.0, 1.0, 0.0] },
+        { "pos": [0.0, 0.5], "color": [0.0, 0.0, 1.0] }
+    ]"#;
+
+    fn scene_with_indices(name: &str, indices: &str) -> TempFile {
+        TempFile::new(
+            &format!("scene-{}", name),
+            "scene.json",
+            &format!(r#"{{ "vertices": {}, "indices": {} }}"#, VERTICES, indices),
+        )
+    }
+
+    #[test]
+    fn loads_the_example_scene() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/triangle.json");
+        let scene = Scene::load(&path).unwrap();
+        assert_eq!(scene.name, path.display().to_string());
+        assert_eq!(scene.indices, [0, 1, 2]);
+        assert_eq!(scene.vertices.len(), 3);
+        let vertex = &scene.vertices[2];
+        assert_eq!(vertex.pos, Vec3::new(0.0, 0.5, 0.0));
+        assert_eq!(vertex.normal, Vec3::Z);
+        assert_eq!(vertex.color, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn loads_shared_vertices() {
+        let file = scene_with_indices("shared", "[0, 1, 2, 2, 1, 0]");
+        let scene = Scene::load(file.path()).unwrap();
+        assert_eq!(scene.indices, [0, 1, 2, 2, 1, 0]);
+    }
+
+    #[test]
+    fn rejects_empty_indices() {
+        let scene = scene_with_indices("empty", "[]");
+        let error = Scene::load(scene.path()).err().unwrap();
+        assert!(
+            error.ends_with("has 0 indices, expected a positive multiple of 3"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_partial_triangles() {
+        let scene = scene_with_indices("partial", "[0, 1, 2, 0]");
+        let error = Scene::load(scene.path()).err().unwrap();
+        assert!(
+            error.ends_with("has 4 indices, expected a positive multiple of 3"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let scene = scene_with_indices("out-of-range", "[0, 1, 3]");
+        let error = Scene::load(scene.path()).err().unwrap();
+        assert!(
+            error.ends_with("has index 3 but only 3 vertices"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let scene = TempFile::new(
+            "scene-unknown-field",
+            "scene.json",
+            &format!(
+                r#"{{ "vertices": {}, "indices": [0, 1, 2], "normals": [] }}"#,
+                VERTICES
+            ),
+        );
+        let error = Scene::load(scene.path()).err().unwrap();
+        assert!(error.starts_with("Failed to parse scene"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_missing_files() {
+        let path = std::env::temp_dir().join("vkrs-scene-test-missing.json");
+        let error = Scene::load(&path).err().unwrap();
+        assert!(error.starts_with("Failed to read scene"), "{}", error);
+    }
+}
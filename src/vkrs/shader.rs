@@ -1,13 +1,18 @@
-use std::{fs::File, path::Path};
+use std::{io::Cursor, rc::Rc};
 
 use ash::vk;
 
-pub fn read_shader_file(path: &Path) -> Vec<u32> {
-    let mut file = File::open(path).unwrap();
-    ash::util::read_spv(&mut file).unwrap()
+use super::handles::{Device, ShaderModule};
+
+/// Decodes SPIR-V embedded with `include_bytes!`, which need not be aligned to 4 bytes. The
+/// shaders are embedded so that the renderer does not depend on the working directory of the
+/// application it is used from.
+pub fn read_spirv(bytes: &[u8]) -> Vec<u32> {
+    ash::util::read_spv(&mut Cursor::new(bytes)).expect("Failed to decode SPIR-V.")
 }
 
-pub fn create_shader_module(device: &ash::Device, shader_code: &[u32]) -> vk::ShaderModule {
+pub fn create_shader_module(device: &Rc<Device>, shader_code: &[u32]) -> ShaderModule {
     let create_info = vk::ShaderModuleCreateInfo::builder().code(shader_code);
-    unsafe { device.create_shader_module(&create_info, None).unwrap() }
+    let shader_module = unsafe { device.create_shader_module(&create_info, None).unwrap() };
+    ShaderModule::new(device, shader_module)
 }
@@ -0,0 +1,46 @@
+#version 450
+
+// A graph of the most recent frame times, drawn over the scene in linear color before it is
+// tonemapped.
+
+layout(push_constant) uniform PushConstants {
+  // Frame times in quarter milliseconds, one byte each, oldest first. Must match `overlay.rs`.
+  uvec4 frameTimes[8];
+} pushConstants;
+
+layout(location = 0) in vec2 inUv;
+
+layout(location = 0) out vec4 outColor;
+
+const uint NUM_FRAME_TIMES = 128;
+// Frame time at the top of the graph.
+const float MAX_MILLISECONDS = 50.0;
+// Reference lines at 60 and 30 frames per second.
+const float FRAME_60 = 1000.0 / 60.0;
+const float FRAME_30 = 1000.0 / 30.0;
+
+float frameTime(uint index) {
+  uint word = pushConstants.frameTimes[index / 16][(index / 4) % 4];
+  return float((word >> (8 * (index % 4))) & 0xff) / 4.0;
+}
+
+void main() {
+  uint index = min(uint(inUv.x * NUM_FRAME_TIMES), NUM_FRAME_TIMES - 1);
+  float milliseconds = frameTime(index);
+  float height = (1.0 - inUv.y) * MAX_MILLISECONDS;
+  float pixel = fwidth(height);
+
+  if (abs(height - FRAME_60) < pixel || abs(height - FRAME_30) < pixel) {
+    outColor = vec4(1.0, 1.0, 1.0, 0.6);
+  } else if (height < milliseconds) {
+    if (milliseconds <= FRAME_60) {
+      outColor = vec4(0.1, 0.8, 0.1, 0.9);
+    } else if (milliseconds <= FRAME_30) {
+      outColor = vec4(0.9, 0.7, 0.1, 0.9);
+    } else {
+      outColor = vec4(0.9, 0.1, 0.1, 0.9);
+    }
+  } else {
+    outColor = vec4(0.0, 0.0, 0.0, 0.6);
+  }
+}
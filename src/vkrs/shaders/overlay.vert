@@ -0,0 +1,10 @@
+#version 450
+
+// A single triangle that covers the whole viewport, which is set to the overlay rectangle.
+layout(location = 0) out vec2 outUv;
+
+void main() {
+  vec2 position = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+  outUv = position;
+  gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
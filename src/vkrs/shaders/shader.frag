@@ -1,9 +1,55 @@
 #version 450
+// Allows `debugPrintfEXT("value %f", value);`, which is logged to the `shader` target when run with
+// `--validation debug-printf`.
+#extension GL_EXT_debug_printf : enable
 
-layout (location = 0) in vec3 fragColor;
+// Must match `MAX_LIGHTS` in light.rs.
+const uint MAX_LIGHTS = 8;
+
+struct Light {
+  // w is 1 for point lights, which have a position, and 0 for directional lights, which have a
+  // direction.
+  vec4 position;
+  vec4 color;
+};
+
+layout(binding = 0) uniform UniformBufferObject {
+  mat4 view;
+  mat4 proj;
+  vec4 ambient;
+  uvec4 lightCount;
+  Light lights[MAX_LIGHTS];
+} ubo;
+
+layout(push_constant) uniform PushConstants {
+  mat4 model;
+  vec4 baseColor;
+  vec4 emissive;
+} object;
+
+layout (location = 0) in vec3 fragPosition;
+layout (location = 1) in vec3 fragNormal;
+layout (location = 2) in vec3 fragColor;
 
 layout (location = 0) out vec4 outColor;
 
 void main() {
-  outColor = vec4(fragColor, 1.0);
+  vec3 normal = normalize(fragNormal);
+  vec3 light = ubo.ambient.rgb;
+  for (uint i = 0; i < min(ubo.lightCount.x, MAX_LIGHTS); i++) {
+    Light l = ubo.lights[i];
+    vec3 toLight;
+    float attenuation;
+    if (l.position.w == 0.0) {
+      toLight = -l.position.xyz;
+      attenuation = 1.0;
+    } else {
+      toLight = l.position.xyz - fragPosition;
+      attenuation = 1.0 / max(dot(toLight, toLight), 1e-4);
+      toLight = normalize(toLight);
+    }
+    light += l.color.rgb * max(dot(normal, toLight), 0.0) * attenuation;
+  }
+  vec3 albedo = fragColor * object.baseColor.rgb;
+  outColor = vec4(albedo * light + object.emissive.rgb, object.baseColor.a);
 }
@@ -1,17 +1,43 @@
 #version 450
+// Allows `debugPrintfEXT("value %f", value);`, which is logged to the `shader` target when run with
+// `--validation debug-printf`.
+#extension GL_EXT_debug_printf : enable
+
+const uint MAX_LIGHTS = 8;
+
+struct Light {
+  // w is 1 for point lights, which have a position, and 0 for directional lights, which have a
+  // direction.
+  vec4 position;
+  vec4 color;
+};
 
 layout(binding = 0) uniform UniformBufferObject {
-  mat4 model;
   mat4 view;
   mat4 proj;
+  vec4 ambient;
+  uvec4 lightCount;
+  Light lights[MAX_LIGHTS];
 } ubo;
 
-layout(location = 0) in vec2 inPosition;
-layout(location = 1) in vec3 inColor;
+layout(push_constant) uniform PushConstants {
+  mat4 model;
+  vec4 baseColor;
+  vec4 emissive;
+} object;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inNormal;
+layout(location = 2) in vec3 inColor;
 
-layout(location = 0) out vec3 fragColor;
+layout(location = 0) out vec3 fragPosition;
+layout(location = 1) out vec3 fragNormal;
+layout(location = 2) out vec3 fragColor;
 
 void main() {
-  gl_Position = ubo.proj * ubo.view * ubo.model * vec4(inPosition, 0.0, 1.0);
+  vec4 worldPosition = object.model * vec4(inPosition, 1.0);
+  gl_Position = ubo.proj * ubo.view * worldPosition;
+  fragPosition = worldPosition.xyz;
+  fragNormal = transpose(inverse(mat3(object.model))) * inNormal;
   fragColor = inColor;
 }
@@ -0,0 +1,58 @@
+#version 450
+
+// Must match `OutputEncoding` in tonemap.rs.
+const uint ENCODING_SRGB = 0u;
+const uint ENCODING_SRGB_IN_SHADER = 1u;
+const uint ENCODING_HDR10 = 2u;
+const uint ENCODING_SCRGB = 3u;
+
+// Linear BT.709 to BT.2020 primaries, column major.
+const mat3 BT709_TO_BT2020 = mat3(
+  0.6274040, 0.0690970, 0.0163916,
+  0.3292820, 0.9195400, 0.0880132,
+  0.0433136, 0.0113612, 0.8955950);
+
+layout(binding = 0) uniform sampler2D scene;
+
+layout(push_constant) uniform Constants {
+  uint encoding;
+  // Luminance in nits that a scene value of 1.0 is displayed at on HDR outputs.
+  float paperWhiteNits;
+} constants;
+
+layout(location = 0) out vec4 outColor;
+
+vec3 srgbEncode(vec3 color) {
+  vec3 low = color * 12.92;
+  vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+  return mix(low, high, greaterThan(color, vec3(0.0031308)));
+}
+
+// SMPTE ST 2084 inverse EOTF, from absolute luminance in nits.
+vec3 pqEncode(vec3 nits) {
+  const float m1 = 0.1593017578125;
+  const float m2 = 78.84375;
+  const float c1 = 0.8359375;
+  const float c2 = 18.8515625;
+  const float c3 = 18.6875;
+  vec3 y = pow(clamp(nits / 10000.0, 0.0, 1.0), vec3(m1));
+  return pow((c1 + c2 * y) / (1.0 + c3 * y), vec3(m2));
+}
+
+void main() {
+  // The scene is the same size as the swapchain.
+  vec3 color = max(texelFetch(scene, ivec2(gl_FragCoord.xy), 0).rgb, vec3(0.0));
+
+  if (constants.encoding == ENCODING_HDR10) {
+    color = pqEncode(BT709_TO_BT2020 * color * constants.paperWhiteNits);
+  } else if (constants.encoding == ENCODING_SCRGB) {
+    color = color * (constants.paperWhiteNits / 80.0);
+  } else {
+    // SDR outputs clip at paper white, which the scene does not go beyond yet.
+    color = min(color, vec3(1.0));
+    if (constants.encoding == ENCODING_SRGB_IN_SHADER) {
+      color = srgbEncode(color);
+    }
+  }
+  outColor = vec4(color, 1.0);
+}
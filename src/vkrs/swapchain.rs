@@ -1,6 +1,134 @@
 use ash::vk;
 
-use crate::vkrs::queue_family_indices::QueueFamilyIndices;
+use std::rc::Rc;
+
+use super::handles::{Device, ImageView, Surface, Swapchain};
+use super::queue_family_indices::QueueFamilyIndices;
+use super::vulkan;
+
+/// Which present mode to use, falling back to FIFO, which is always supported, if the preferred
+/// modes are not available.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PresentModePreference {
+    /// FIFO, which waits for vertical blank and never tears.
+    On,
+    /// IMMEDIATE, or MAILBOX if that is not supported. Uncapped, for benchmarking.
+    Off,
+    /// FIFO_RELAXED, which tears rather than waiting when a frame is late.
+    Adaptive,
+    /// MAILBOX, which never tears but replaces queued images with newer ones.
+    #[default]
+    LowLatency,
+}
+
+impl PresentModePreference {
+    pub const ALL: [Self; 4] = [Self::On, Self::Off, Self::Adaptive, Self::LowLatency];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|preference| preference.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::On => "on",
+            Self::Off => "off",
+            Self::Adaptive => "adaptive",
+            Self::LowLatency => "low-latency",
+        }
+    }
+
+    /// Returns the preference after this one, for cycling through them with a hotkey.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|p| *p == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn present_modes(self) -> &'static [vk::PresentModeKHR] {
+        match self {
+            Self::On => &[vk::PresentModeKHR::FIFO],
+            Self::Off => &[vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX],
+            Self::Adaptive => &[vk::PresentModeKHR::FIFO_RELAXED],
+            Self::LowLatency => &[vk::PresentModeKHR::MAILBOX],
+        }
+    }
+}
+
+/// Whether to present in an HDR color space, falling back to SDR if the surface does not support
+/// the requested one. HDR color spaces are only reported when `VK_EXT_swapchain_colorspace` is
+/// enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HdrMode {
+    /// 8-bit sRGB.
+    #[default]
+    Off,
+    /// 10-bit BT.2020 primaries with the PQ transfer function.
+    Hdr10,
+    /// 16-bit float extended sRGB with linear encoding, where 1.0 is 80 nits.
+    ScRgb,
+}
+
+impl HdrMode {
+    pub const ALL: [Self; 3] = [Self::Off, Self::Hdr10, Self::ScRgb];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|mode| mode.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Hdr10 => "hdr10",
+            Self::ScRgb => "scrgb",
+        }
+    }
+
+    /// Candidate formats and color spaces, in order of preference.
+    fn surface_formats(self) -> &'static [(vk::Format, vk::ColorSpaceKHR)] {
+        match self {
+            Self::Off => &[
+                (vk::Format::B8G8R8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+                (vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR),
+            ],
+            Self::Hdr10 => &[
+                (
+                    vk::Format::A2B10G10R10_UNORM_PACK32,
+                    vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+                ),
+                (
+                    vk::Format::A2R10G10B10_UNORM_PACK32,
+                    vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+                ),
+            ],
+            Self::ScRgb => &[(
+                vk::Format::R16G16B16A16_SFLOAT,
+                vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            )],
+        }
+    }
+}
+
+/// What the swapchain is created with, as close to this as the surface supports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SwapchainPreferences {
+    pub present_mode: PresentModePreference,
+    pub hdr: HdrMode,
+    /// Number of swapchain images, 2 for double and 3 for triple buffering. Defaults to one more
+    /// than the minimum the surface requires.
+    pub image_count: Option<u32>,
+}
+
+impl SwapchainPreferences {
+    /// Switches between double and triple buffering.
+    pub fn toggle_buffering(&mut self) {
+        self.image_count = match self.image_count {
+            Some(2) => Some(3),
+            _ => Some(2),
+        };
+    }
+}
 
 pub struct SupportDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
@@ -47,10 +175,12 @@ impl SupportDetails {
 
     pub fn get_ideal_swapchain_properties(
         &self,
-        window_size: &winit::dpi::PhysicalSize<u32>,
+        window_size: vk::Extent2D,
+        preferences: SwapchainPreferences,
     ) -> SwapchainProperties {
-        let surface_format = Self::choose_swapchain_surface_format(&self.formats);
-        let present_mode = Self::choose_swapchain_present_mode(&self.present_modes);
+        let surface_format = Self::choose_swapchain_surface_format(&self.formats, preferences.hdr);
+        let present_mode =
+            Self::choose_swapchain_present_mode(&self.present_modes, preferences.present_mode);
         let extent = Self::choose_swapchain_extent(self.capabilities, window_size);
         SwapchainProperties {
             surface_format,
@@ -61,29 +191,51 @@ impl SupportDetails {
 
     fn choose_swapchain_surface_format(
         available_formats: &[vk::SurfaceFormatKHR],
+        hdr: HdrMode,
     ) -> vk::SurfaceFormatKHR {
-        *available_formats
-            .iter()
-            .find(|format| {
-                format.format == vk::Format::B8G8R8A8_SRGB
-                    && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        let find = |mode: HdrMode| {
+            mode.surface_formats().iter().find_map(|candidate| {
+                available_formats
+                    .iter()
+                    .find(|format| (format.format, format.color_space) == *candidate)
+                    .copied()
+            })
+        };
+        if let Some(format) = find(hdr) {
+            return format;
+        }
+        if hdr != HdrMode::Off {
+            log::warn!(target: "vkrs",
+                       "HDR mode {} is not supported by the surface, falling back to SDR",
+                       hdr.name());
+        }
+        find(HdrMode::Off)
+            // Any sRGB format will do, the tonemap pass encodes for it if the format does not.
+            .or_else(|| {
+                available_formats
+                    .iter()
+                    .find(|format| format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+                    .copied()
             })
-            .unwrap_or(&available_formats[0])
+            .unwrap_or(available_formats[0])
     }
 
     fn choose_swapchain_present_mode(
         available_present_modes: &[vk::PresentModeKHR],
+        preference: PresentModePreference,
     ) -> vk::PresentModeKHR {
-        if available_present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-            return vk::PresentModeKHR::MAILBOX;
-        }
-        // FIFO is guaranteed to exist, so it is used as fallback.
-        vk::PresentModeKHR::FIFO
+        preference
+            .present_modes()
+            .iter()
+            .find(|present_mode| available_present_modes.contains(present_mode))
+            .copied()
+            // FIFO is guaranteed to exist, so it is used as fallback.
+            .unwrap_or(vk::PresentModeKHR::FIFO)
     }
 
     fn choose_swapchain_extent(
         capabilities: vk::SurfaceCapabilitiesKHR,
-        window_size: &winit::dpi::PhysicalSize<u32>,
+        window_size: vk::Extent2D,
     ) -> vk::Extent2D {
         if capabilities.current_extent.width != std::u32::MAX {
             return capabilities.current_extent;
@@ -99,33 +251,51 @@ impl SupportDetails {
     }
 }
 
+/// The extent the surface requires swapchains to have, if it does not leave it to the swapchain.
+pub fn surface_extent(
+    physical_device: vk::PhysicalDevice,
+    surface: &Surface,
+) -> Option<vk::Extent2D> {
+    let capabilities = unsafe {
+        surface
+            .loader()
+            .get_physical_device_surface_capabilities(physical_device, surface.handle())
+            .expect("Failed to get surface capabilities.")
+    };
+    Some(capabilities.current_extent).filter(|extent| extent.width != u32::MAX)
+}
+
 pub fn create_swapchain_and_images(
-    instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-    device: &ash::Device,
-    surface_fn: &ash::extensions::khr::Surface,
-    surface: vk::SurfaceKHR,
+    device: &Rc<Device>,
+    surface: &Rc<Surface>,
     queue_family_indices: &QueueFamilyIndices,
-    window_size: &winit::dpi::PhysicalSize<u32>,
-    old_swapchain_khr: Option<vk::SwapchainKHR>,
+    window_size: vk::Extent2D,
+    preferences: SwapchainPreferences,
+    old_swapchain: Option<&Swapchain>,
 ) -> (
-    ash::extensions::khr::Swapchain,
-    vk::SwapchainKHR,
-    vk::Format,
+    Swapchain,
+    vk::SurfaceFormatKHR,
     vk::Extent2D,
     Vec<vk::Image>,
 ) {
-    let swapchain_support_details = SupportDetails::new(physical_device, surface_fn, surface);
-    let properties = swapchain_support_details.get_ideal_swapchain_properties(window_size);
-    let image_count = {
-        let mut preferred_num_images = swapchain_support_details.capabilities.min_image_count + 1;
-        let max_num_images = swapchain_support_details.capabilities.max_image_count;
-        let unlimited_max_num_images = max_num_images == 0;
-        if !unlimited_max_num_images && preferred_num_images > max_num_images {
-            preferred_num_images = max_num_images;
-        }
-        preferred_num_images
-    };
+    let swapchain_support_details =
+        SupportDetails::new(physical_device, surface.loader(), surface.handle());
+    let properties =
+        swapchain_support_details.get_ideal_swapchain_properties(window_size, preferences);
+    log::info!(target: "vkrs",
+               "Using present mode {:?} for vsync {}",
+               properties.present_mode,
+               preferences.present_mode.name());
+    log::info!(target: "vkrs",
+               "Using format {:?} in color space {:?} for HDR mode {}",
+               properties.surface_format.format,
+               properties.surface_format.color_space,
+               preferences.hdr.name());
+    let image_count = choose_image_count(
+        swapchain_support_details.capabilities,
+        preferences.image_count,
+    );
 
     log::debug!(target: "vulkan",
                 concat!("Creating swapchain:\n",
@@ -146,7 +316,7 @@ pub fn create_swapchain_and_images(
 
     let create_info = {
         let mut builder = vk::SwapchainCreateInfoKHR::builder()
-            .surface(surface)
+            .surface(surface.handle())
             .min_image_count(image_count)
             .image_format(properties.surface_format.format)
             .image_color_space(properties.surface_format.color_space)
@@ -166,51 +336,69 @@ pub fn create_swapchain_and_images(
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(properties.present_mode)
             .clipped(true);
-        if let Some(old_swapchain_khr) = old_swapchain_khr {
-            builder = builder.old_swapchain(old_swapchain_khr);
+        if let Some(old_swapchain) = old_swapchain {
+            builder = builder.old_swapchain(old_swapchain.handle());
         }
         builder
     };
 
-    let swapchain = ash::extensions::khr::Swapchain::new(instance, device);
-    let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None).unwrap() };
-    let images = unsafe { swapchain.get_swapchain_images(swapchain_khr).unwrap() };
+    let loader = ash::extensions::khr::Swapchain::new(device.instance(), device);
+    let swapchain_khr = unsafe { loader.create_swapchain(&create_info, None).unwrap() };
+    let swapchain = Swapchain::new(device, surface, loader, swapchain_khr);
+    let images = unsafe {
+        swapchain
+            .loader()
+            .get_swapchain_images(swapchain_khr)
+            .unwrap()
+    };
+    log::info!(target: "vkrs", "Created swapchain with {} images", images.len());
+    swapchain.set_name("swapchain");
+    for (index, image) in images.iter().enumerate() {
+        device.set_object_name(*image, &format!("swapchain image {}", index));
+    }
     (
         swapchain,
-        swapchain_khr,
-        properties.surface_format.format,
+        properties.surface_format,
         properties.extent,
         images,
     )
 }
 
+/// Clamps the preferred image count to what the surface supports.
+fn choose_image_count(capabilities: vk::SurfaceCapabilitiesKHR, preferred: Option<u32>) -> u32 {
+    let min_num_images = capabilities.min_image_count;
+    let max_num_images = capabilities.max_image_count;
+    let unlimited_max_num_images = max_num_images == 0;
+    let preferred_num_images = preferred.unwrap_or(min_num_images + 1);
+    let mut num_images = preferred_num_images.max(min_num_images);
+    if !unlimited_max_num_images && num_images > max_num_images {
+        num_images = max_num_images;
+    }
+    if preferred.is_some() && num_images != preferred_num_images {
+        log::warn!(target: "vkrs",
+                   "{} swapchain images requested, but the surface supports {} to {}, using {}",
+                   preferred_num_images,
+                   min_num_images,
+                   if unlimited_max_num_images { "unlimited".to_owned() } else { max_num_images.to_string() },
+                   num_images);
+    }
+    num_images
+}
+
 pub fn create_image_views(
-    device: &ash::Device,
+    device: &Rc<Device>,
     swapchain_images: &[vk::Image],
     swapchain_image_format: vk::Format,
-) -> Vec<vk::ImageView> {
+) -> Vec<ImageView> {
     swapchain_images
         .iter()
         .map(|image| {
-            let create_info = vk::ImageViewCreateInfo::builder()
-                .image(*image)
-                .view_type(vk::ImageViewType::TYPE_2D)
-                .format(swapchain_image_format)
-                .components(vk::ComponentMapping {
-                    r: vk::ComponentSwizzle::IDENTITY,
-                    g: vk::ComponentSwizzle::IDENTITY,
-                    b: vk::ComponentSwizzle::IDENTITY,
-                    a: vk::ComponentSwizzle::IDENTITY,
-                })
-                .subresource_range(vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    base_mip_level: 0,
-                    level_count: 1,
-                    base_array_layer: 0,
-                    layer_count: 1,
-                });
-
-            unsafe { device.create_image_view(&create_info, None).unwrap() }
+            vulkan::create_image_view(
+                device,
+                *image,
+                swapchain_image_format,
+                vk::ImageAspectFlags::COLOR,
+            )
         })
         .collect::<Vec<_>>()
 }
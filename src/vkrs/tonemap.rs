@@ -0,0 +1,516 @@
+use super::frame_stats::DrawStats;
+use super::handles::{
+    DescriptorPool, DescriptorSetLayout, Device, Framebuffer, Image, ImageView, Pipeline,
+    PipelineLayout, RenderPass, Sampler,
+};
+use super::shader;
+use super::swapchain;
+use super::vulkan;
+
+use ash::vk;
+
+use std::{ffi::CString, mem::size_of, rc::Rc, slice};
+
+/// Format of the offscreen image the scene is rendered to, in linear BT.709 where 1.0 is paper
+/// white.
+pub const SCENE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Luminance that paper white is displayed at on HDR outputs, the BT.2408 reference level.
+const PAPER_WHITE_NITS: f32 = 203.0;
+
+/// How the tonemap pass encodes the scene for the swapchain color space. The values must match
+/// the `ENCODING_` constants in `tonemap.frag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum OutputEncoding {
+    /// sRGB, with the transfer function applied by the `_SRGB` swapchain format.
+    Srgb = 0,
+    /// sRGB, with the transfer function applied in the shader for `_UNORM` swapchain formats.
+    SrgbInShader = 1,
+    /// BT.2020 primaries with the PQ transfer function.
+    Hdr10 = 2,
+    /// Linear extended sRGB, where 1.0 is 80 nits.
+    ScRgb = 3,
+}
+
+impl OutputEncoding {
+    pub fn for_surface_format(surface_format: vk::SurfaceFormatKHR) -> Self {
+        match surface_format.color_space {
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => Self::Hdr10,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => Self::ScRgb,
+            _ => match surface_format.format {
+                vk::Format::B8G8R8A8_SRGB
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::A8B8G8R8_SRGB_PACK32 => Self::Srgb,
+                _ => Self::SrgbInShader,
+            },
+        }
+    }
+}
+
+#[repr(C)]
+struct PushConstants {
+    encoding: u32,
+    paper_white_nits: f32,
+}
+
+/// The HDR image and the depth image the scene is rendered to, and the pass that tonemaps it into
+/// the swapchain images. Everything here depends on the swapchain, so it is recreated with it.
+pub struct TonemapPass {
+    encoding: OutputEncoding,
+    framebuffers: Vec<Framebuffer>,
+    pipeline: Pipeline,
+    pipeline_layout: PipelineLayout,
+    render_pass: RenderPass,
+    // The descriptor set is freed together with its pool.
+    descriptor_set: vk::DescriptorSet,
+    _descriptor_pool: DescriptorPool,
+    _descriptor_set_layout: DescriptorSetLayout,
+    _sampler: Sampler,
+    scene_framebuffer: Framebuffer,
+    // The multisampled image the scene is rendered to and resolved from, with MSAA.
+    _multisampled_scene_image_view: Option<ImageView>,
+    _multisampled_scene_image: Option<Image>,
+    _depth_image_view: ImageView,
+    _depth_image: Image,
+    _scene_image_view: ImageView,
+    _scene_image: Image,
+}
+
+impl TonemapPass {
+    /// `scene_render_pass` is the pass the scene is rendered with, created by
+    /// [`vulkan::create_render_pass`] for [`SCENE_FORMAT`] with `scene_samples`,
+    /// `SHADER_READ_ONLY_OPTIMAL` and `depth_format`. The swapchain images are left in `final_layout`, which is
+    /// `PRESENT_SRC_KHR` unless they are offscreen images that are read back instead.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &Rc<Device>,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        scene_render_pass: vk::RenderPass,
+        scene_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+        swapchain_surface_format: vk::SurfaceFormatKHR,
+        swapchain_image_views: &[ImageView],
+        final_layout: vk::ImageLayout,
+        swapchain_extent: vk::Extent2D,
+    ) -> Self {
+        let scene_image = vulkan::create_image(
+            device,
+            memory_properties,
+            swapchain_extent,
+            SCENE_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::SampleCountFlags::TYPE_1,
+        );
+        let scene_image_view =
+            swapchain::create_image_views(device, &[scene_image.handle()], SCENE_FORMAT)
+                .pop()
+                .unwrap();
+        let (multisampled_scene_image, multisampled_scene_image_view) = if scene_samples
+            == vk::SampleCountFlags::TYPE_1
+        {
+            (None, None)
+        } else {
+            // Only used within the render pass, the samples are never stored.
+            let image = vulkan::create_image(
+                device,
+                memory_properties,
+                swapchain_extent,
+                SCENE_FORMAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                scene_samples,
+            );
+            let view = swapchain::create_image_views(device, &[image.handle()], SCENE_FORMAT)
+                .pop()
+                .unwrap();
+            image.set_name("multisampled scene color image");
+            view.set_name("multisampled scene color image view");
+            (Some(image), Some(view))
+        };
+        // Only used within the render pass, the depth is never stored.
+        let depth_image = vulkan::create_image(
+            device,
+            memory_properties,
+            swapchain_extent,
+            depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            scene_samples,
+        );
+        let depth_image_view = vulkan::create_image_view(
+            device,
+            depth_image.handle(),
+            depth_format,
+            vk::ImageAspectFlags::DEPTH,
+        );
+        let scene_attachments = match &multisampled_scene_image_view {
+            Some(view) => vec![
+                view.handle(),
+                depth_image_view.handle(),
+                scene_image_view.handle(),
+            ],
+            None => vec![scene_image_view.handle(), depth_image_view.handle()],
+        };
+        let scene_framebuffer = vulkan::create_framebuffer(
+            device,
+            &scene_attachments,
+            scene_render_pass,
+            swapchain_extent,
+        );
+
+        let sampler = create_sampler(device);
+        let descriptor_set_layout = create_descriptor_set_layout(device);
+        let descriptor_pool = create_descriptor_pool(device);
+        let descriptor_set = create_descriptor_set(
+            device,
+            descriptor_pool.handle(),
+            descriptor_set_layout.handle(),
+            scene_image_view.handle(),
+            sampler.handle(),
+        );
+
+        let render_pass = vulkan::create_render_pass(
+            device,
+            swapchain_surface_format.format,
+            final_layout,
+            vk::SampleCountFlags::TYPE_1,
+            None,
+        );
+        let (pipeline, pipeline_layout) = create_pipeline(
+            device,
+            swapchain_extent,
+            render_pass.handle(),
+            descriptor_set_layout.handle(),
+        );
+        let framebuffers = vulkan::create_framebuffers(
+            device,
+            swapchain_image_views,
+            render_pass.handle(),
+            swapchain_extent,
+        );
+
+        scene_image.set_name("scene color image");
+        scene_image_view.set_name("scene color image view");
+        depth_image.set_name("scene depth image");
+        depth_image_view.set_name("scene depth image view");
+        scene_framebuffer.set_name("scene framebuffer");
+        sampler.set_name("tonemap sampler");
+        descriptor_set_layout.set_name("tonemap descriptor set layout");
+        descriptor_pool.set_name("tonemap descriptor pool");
+        device.set_object_name(descriptor_set, "tonemap descriptor set");
+        render_pass.set_name("tonemap render pass");
+        pipeline.set_name("tonemap pipeline");
+        pipeline_layout.set_name("tonemap pipeline layout");
+        for (index, framebuffer) in framebuffers.iter().enumerate() {
+            framebuffer.set_name(&format!("tonemap framebuffer {}", index));
+        }
+
+        let encoding = OutputEncoding::for_surface_format(swapchain_surface_format);
+        log::debug!(target: "vkrs", "Tonemapping to {:?}", encoding);
+
+        Self {
+            encoding,
+            framebuffers,
+            pipeline,
+            pipeline_layout,
+            render_pass,
+            descriptor_set,
+            _descriptor_pool: descriptor_pool,
+            _descriptor_set_layout: descriptor_set_layout,
+            _sampler: sampler,
+            scene_framebuffer,
+            _multisampled_scene_image_view: multisampled_scene_image_view,
+            _multisampled_scene_image: multisampled_scene_image,
+            _depth_image_view: depth_image_view,
+            _depth_image: depth_image,
+            _scene_image_view: scene_image_view,
+            _scene_image: scene_image,
+        }
+    }
+
+    /// The framebuffer the scene render pass renders to.
+    pub fn scene_framebuffer(&self) -> vk::Framebuffer {
+        self.scene_framebuffer.handle()
+    }
+
+    /// Records the tonemap pass into `command_buffer`, which must be recording, after the scene
+    /// render pass.
+    pub fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        swapchain_extent: vk::Extent2D,
+    ) -> DrawStats {
+        let clear_values = [vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        }];
+        let render_pass_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass.handle())
+            .framebuffer(self.framebuffers[image_index as usize].handle())
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: swapchain_extent,
+            })
+            .clear_values(&clear_values);
+        let push_constants = PushConstants {
+            encoding: self.encoding as u32,
+            paper_white_nits: PAPER_WHITE_NITS,
+        };
+        let push_constants = unsafe {
+            slice::from_raw_parts(
+                &push_constants as *const PushConstants as *const u8,
+                size_of::<PushConstants>(),
+            )
+        };
+        device.begin_label(command_buffer, "tonemap pass");
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline.handle(),
+            );
+            let descriptor_sets = [self.descriptor_set];
+            let dynamic_offsets = [];
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline_layout.handle(),
+                0,
+                &descriptor_sets,
+                &dynamic_offsets,
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.pipeline_layout.handle(),
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                push_constants,
+            );
+            // A single triangle covering the screen, generated in the vertex shader.
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.cmd_end_render_pass(command_buffer);
+        }
+        device.end_label(command_buffer);
+        DrawStats {
+            draw_calls: 1,
+            triangles: 1,
+            pipeline_binds: 1,
+            descriptor_binds: 1,
+        }
+    }
+}
+
+fn create_sampler(device: &Rc<Device>) -> Sampler {
+    // The scene is the same size as the swapchain and read with `texelFetch`, so there is no
+    // filtering.
+    let sampler_info = vk::SamplerCreateInfo::builder()
+        .mag_filter(vk::Filter::NEAREST)
+        .min_filter(vk::Filter::NEAREST)
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+        .max_lod(0.0);
+    let sampler = unsafe {
+        device
+            .create_sampler(&sampler_info, None)
+            .expect("Failed to create sampler.")
+    };
+    Sampler::new(device, sampler)
+}
+
+fn create_descriptor_set_layout(device: &Rc<Device>) -> DescriptorSetLayout {
+    let scene_layout_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .build();
+    let bindings = [scene_layout_binding];
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+
+    let layout = unsafe {
+        device
+            .create_descriptor_set_layout(&layout_info, None)
+            .expect("Failed to create descriptor set layout.")
+    };
+    DescriptorSetLayout::new(device, layout)
+}
+
+fn create_descriptor_pool(device: &Rc<Device>) -> DescriptorPool {
+    let pool_size = vk::DescriptorPoolSize::builder()
+        .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .descriptor_count(1)
+        .build();
+    let pool_sizes = [pool_size];
+
+    let pool_info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(&pool_sizes)
+        .max_sets(1);
+
+    let descriptor_pool = unsafe {
+        device
+            .create_descriptor_pool(&pool_info, None)
+            .expect("Failed to create descriptor pool.")
+    };
+    DescriptorPool::new(device, descriptor_pool)
+}
+
+fn create_descriptor_set(
+    device: &ash::Device,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    scene_image_view: vk::ImageView,
+    sampler: vk::Sampler,
+) -> vk::DescriptorSet {
+    let layouts = [descriptor_set_layout];
+    let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&layouts);
+    let descriptor_set = unsafe {
+        device
+            .allocate_descriptor_sets(&alloc_info)
+            .expect("Failed to allocate descriptor sets.")[0]
+    };
+
+    let image_info = vk::DescriptorImageInfo::builder()
+        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .image_view(scene_image_view)
+        .sampler(sampler)
+        .build();
+    let image_infos = [image_info];
+
+    let descriptor_write = vk::WriteDescriptorSet::builder()
+        .dst_set(descriptor_set)
+        .dst_binding(0)
+        .dst_array_element(0)
+        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        .image_info(&image_infos)
+        .build();
+    let descriptor_writes = [descriptor_write];
+    let descriptor_copies = [];
+
+    unsafe { device.update_descriptor_sets(&descriptor_writes, &descriptor_copies) };
+    descriptor_set
+}
+
+fn create_pipeline(
+    device: &Rc<Device>,
+    swapchain_extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+) -> (Pipeline, PipelineLayout) {
+    let vertex_shader_code = shader::read_spirv(include_bytes!("shaders/tonemap.vert.spv"));
+    let fragment_shader_code = shader::read_spirv(include_bytes!("shaders/tonemap.frag.spv"));
+
+    let vertex_shader_module = shader::create_shader_module(device, &vertex_shader_code);
+    let fragment_shader_module = shader::create_shader_module(device, &fragment_shader_code);
+    vertex_shader_module.set_name("tonemap.vert");
+    fragment_shader_module.set_name("tonemap.frag");
+
+    let shader_entry_point = CString::new("main").unwrap();
+    let vertex_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::VERTEX)
+        .module(vertex_shader_module.handle())
+        .name(&shader_entry_point)
+        .build();
+    let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::FRAGMENT)
+        .module(fragment_shader_module.handle())
+        .name(&shader_entry_point)
+        .build();
+
+    let shader_stages = [vertex_shader_stage_info, fragment_shader_stage_info];
+
+    // The vertices are generated from their index, there is no vertex input.
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::builder();
+    let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+        .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+        .primitive_restart_enable(false);
+
+    let viewport = vk::Viewport::builder()
+        .x(0.0)
+        .y(0.0)
+        .width(swapchain_extent.width as _)
+        .height(swapchain_extent.height as _)
+        .min_depth(0.0)
+        .max_depth(1.0)
+        .build();
+    let viewports = [viewport];
+    let scissor = vk::Rect2D::builder()
+        .offset(vk::Offset2D { x: 0, y: 0 })
+        .extent(swapchain_extent)
+        .build();
+    let scissors = [scissor];
+    let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+        .viewports(&viewports)
+        .scissors(&scissors);
+
+    let rasterizer = vk::PipelineRasterizationStateCreateInfo::builder()
+        .depth_clamp_enable(false)
+        .rasterizer_discard_enable(false)
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(vk::CullModeFlags::NONE)
+        .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+        .depth_bias_enable(false);
+
+    let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
+        .sample_shading_enable(false)
+        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+        .color_write_mask(vk::ColorComponentFlags::RGBA)
+        .blend_enable(false)
+        .build();
+    let color_blend_attachments = [color_blend_attachment];
+    let color_blending = vk::PipelineColorBlendStateCreateInfo::builder()
+        .logic_op_enable(false)
+        .attachments(&color_blend_attachments);
+
+    let set_layouts = [descriptor_set_layout];
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(size_of::<PushConstants>() as _)
+        .build();
+    let push_constant_ranges = [push_constant_range];
+    let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout = unsafe {
+        device
+            .create_pipeline_layout(&pipeline_layout_info, None)
+            .unwrap()
+    };
+    let pipeline_layout = PipelineLayout::new(device, pipeline_layout);
+
+    let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&shader_stages)
+        .vertex_input_state(&vertex_input_info)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport_state)
+        .rasterization_state(&rasterizer)
+        .multisample_state(&multisampling)
+        .color_blend_state(&color_blending)
+        .layout(pipeline_layout.handle())
+        .render_pass(render_pass)
+        .subpass(0)
+        .build();
+    let pipeline_infos = [pipeline_info];
+    let pipeline = unsafe {
+        device
+            .create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+            .unwrap()[0]
+    };
+
+    (Pipeline::new(device, pipeline), pipeline_layout)
+}
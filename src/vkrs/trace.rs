@@ -0,0 +1,159 @@
+//! CPU spans, and GPU timings from the [`GpuProfiler`](super::gpu_profiler::GpuProfiler),
+//! captured on demand into a Chrome trace that can be opened in Perfetto or `chrome://tracing`.
+//!
+//! Spans are only recorded while a capture is running, otherwise they cost a thread local lookup.
+//! The capture lives in a thread local, everything is recorded on the thread running the event
+//! loop.
+
+use serde::Serialize;
+use serde_json::json;
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const PROCESS_ID: u32 = 1;
+const CPU_THREAD_ID: u32 = 1;
+const GPU_THREAD_ID: u32 = 2;
+
+/// A complete event in the Chrome trace event format, with times in microseconds.
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+struct Capture {
+    start: Instant,
+    events: Vec<TraceEvent>,
+}
+
+impl Capture {
+    fn push(&mut self, name: &'static str, thread_id: u32, start: Instant, duration: Duration) {
+        // Spans that started before the capture are cut off at its start.
+        let ts = start.saturating_duration_since(self.start);
+        let cut_off = self.start.saturating_duration_since(start);
+        let category = if thread_id == GPU_THREAD_ID {
+            "gpu"
+        } else {
+            "cpu"
+        };
+        self.events.push(TraceEvent {
+            name,
+            cat: category,
+            ph: "X",
+            ts: ts.as_secs_f64() * 1e6,
+            dur: duration.saturating_sub(cut_off).as_secs_f64() * 1e6,
+            pid: PROCESS_ID,
+            tid: thread_id,
+        });
+    }
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// Records the time until it is dropped as a span named `name`, if a capture is running.
+#[must_use = "the span ends when it is dropped"]
+pub struct Span {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let duration = start.elapsed();
+            CAPTURE.with(|capture| {
+                if let Some(capture) = capture.borrow_mut().as_mut() {
+                    capture.push(self.name, CPU_THREAD_ID, start, duration);
+                }
+            });
+        }
+    }
+}
+
+pub fn span(name: &'static str) -> Span {
+    Span {
+        name,
+        start: is_capturing().then(Instant::now),
+    }
+}
+
+pub fn is_capturing() -> bool {
+    CAPTURE.with(|capture| capture.borrow().is_some())
+}
+
+/// Adds a GPU scope that ran from `start` for `duration`, on a track of its own.
+pub fn gpu_event(name: &'static str, start: Instant, duration: Duration) {
+    CAPTURE.with(|capture| {
+        if let Some(capture) = capture.borrow_mut().as_mut() {
+            capture.push(name, GPU_THREAD_ID, start, duration);
+        }
+    });
+}
+
+pub fn start_capture() {
+    CAPTURE.with(|capture| {
+        *capture.borrow_mut() = Some(Capture {
+            start: Instant::now(),
+            events: Vec::new(),
+        });
+    });
+    log::info!(target: "vkrs", "Started capturing a trace");
+}
+
+/// Stops the capture, if one is running, and writes it to `vkrs-trace-<unix time>.json` in the
+/// working directory.
+pub fn finish_capture() {
+    let capture = match CAPTURE.with(|capture| capture.borrow_mut().take()) {
+        Some(capture) => capture,
+        None => return,
+    };
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = PathBuf::from(format!("vkrs-trace-{}.json", seconds));
+
+    let thread_name = |thread_id, name| {
+        json!({
+            "name": "thread_name",
+            "ph": "M",
+            "pid": PROCESS_ID,
+            "tid": thread_id,
+            "args": { "name": name },
+        })
+    };
+    let mut events = vec![
+        thread_name(CPU_THREAD_ID, "CPU"),
+        thread_name(GPU_THREAD_ID, "GPU (graphics queue)"),
+    ];
+    events.extend(
+        capture
+            .events
+            .iter()
+            .map(|event| serde_json::to_value(event).expect("Failed to serialize trace event.")),
+    );
+    let trace = json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+    });
+    match fs::write(&path, trace.to_string()) {
+        Ok(()) => log::info!(target: "vkrs",
+                             "Wrote {} trace events to {}",
+                             capture.events.len(),
+                             path.display()),
+        Err(error) => {
+            log::error!(target: "vkrs", "Failed to write trace to {}: {}", path.display(), error)
+        }
+    }
+}
@@ -1,8 +1,28 @@
-use glam::Mat4;
+use super::light::{GpuLight, MAX_LIGHTS};
 
+use glam::{Mat4, Vec4};
+
+/// Per frame data of the scene shaders, laid out like `UniformBufferObject` in `shader.vert` and
+/// `shader.frag` with std140.
 #[derive(Clone, Copy)]
+#[repr(C)]
 pub struct UniformBufferObject {
-    pub model: Mat4,
     pub view: Mat4,
     pub proj: Mat4,
+    /// The sum of the ambient lights.
+    pub ambient: Vec4,
+    /// Only x is used, the rest pads it to 16 bytes.
+    pub light_count: [u32; 4],
+    pub lights: [GpuLight; MAX_LIGHTS],
+}
+
+/// Draws the mesh with the model matrix and the material, laid out like `PushConstants` in
+/// `shader.vert` and `shader.frag`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ObjectPushConstants {
+    pub model: Mat4,
+    pub base_color: Vec4,
+    /// The alpha is unused.
+    pub emissive: Vec4,
 }
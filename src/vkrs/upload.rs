@@ -0,0 +1,233 @@
+use super::gpu_timeline::{GpuTimeline, Submission};
+use super::handles::{Buffer, CommandPool, Device};
+use super::vulkan;
+
+use ash::vk;
+
+use std::{collections::VecDeque, mem::align_of, rc::Rc};
+
+/// Handle to an upload that has been queued with the [`UploadManager`]. It becomes ready once
+/// the batch it was recorded into has been submitted, work submitted after that to the same
+/// queue is ordered after the copies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UploadHandle {
+    batch: u64,
+}
+
+struct Batch {
+    id: u64,
+    command_buffer: vk::CommandBuffer,
+    // GPU progress value signaled by the batch, only known once it has been submitted.
+    timeline_value: u64,
+    staging_buffers: Vec<Buffer>,
+}
+
+/// Records staging copies into a single command buffer per frame and tracks their completion
+/// on the [`GpuTimeline`], so that uploads do not have to stall the CPU with `queue_wait_idle`.
+pub struct UploadManager {
+    device: Rc<Device>,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    command_pool: CommandPool,
+    recording: Option<Batch>,
+    in_flight: VecDeque<Batch>,
+    next_batch: u64,
+}
+
+impl UploadManager {
+    pub fn new(
+        device: &Rc<Device>,
+        memory_properties: vk::PhysicalDeviceMemoryProperties,
+        command_pool: CommandPool,
+    ) -> Self {
+        Self {
+            device: Rc::clone(device),
+            memory_properties,
+            command_pool,
+            recording: None,
+            in_flight: VecDeque::new(),
+            next_batch: 1,
+        }
+    }
+
+    /// Creates a device local buffer named `name` and queues a copy of `data` into it. The buffer
+    /// must not be used by the GPU until the returned handle is ready, and then only on the queue
+    /// the uploads are submitted to.
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        name: &str,
+        buffer_usage_flags: vk::BufferUsageFlags,
+        data: &[T],
+    ) -> (Buffer, UploadHandle) {
+        let buffer_size = std::mem::size_of_val(data) as vk::DeviceSize;
+
+        // TODO(lovew): Instead of creating a buffer here we could have implemented a memory
+        // allocator that we would request memory from, and it would give us a chunk of memory
+        // that was bound to a buffer and mapped to some host memory.
+        let staging_buffer = vulkan::create_buffer(
+            &self.device,
+            self.memory_properties,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging_buffer.set_name(&format!("{} staging", name));
+
+        unsafe {
+            let data_ptr = self
+                .device
+                .map_memory(
+                    staging_buffer.memory(),
+                    0,
+                    buffer_size,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .expect("Failed to map staging buffer memory.");
+            let mut align =
+                ash::util::Align::new(data_ptr, align_of::<T>() as _, staging_buffer.memory_size());
+            align.copy_from_slice(data);
+            self.device.unmap_memory(staging_buffer.memory());
+        }
+
+        let buffer = vulkan::create_buffer(
+            &self.device,
+            self.memory_properties,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST | buffer_usage_flags,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        buffer.set_name(name);
+
+        let device = Rc::clone(&self.device);
+        let batch = self.recording_batch();
+        let copy_region = vk::BufferCopy::builder()
+            .src_offset(0)
+            .dst_offset(0)
+            .size(buffer_size)
+            .build();
+        let regions = [copy_region];
+        device.begin_label(batch.command_buffer, &format!("upload {}", name));
+        unsafe {
+            device.cmd_copy_buffer(
+                batch.command_buffer,
+                staging_buffer.handle(),
+                buffer.handle(),
+                &regions,
+            );
+        }
+        device.end_label(batch.command_buffer);
+        batch.staging_buffers.push(staging_buffer);
+
+        let handle = UploadHandle { batch: batch.id };
+        (buffer, handle)
+    }
+
+    fn recording_batch(&mut self) -> &mut Batch {
+        if self.recording.is_none() {
+            let command_buffer =
+                vulkan::create_command_buffers(&self.device, self.command_pool.handle(), 1)[0];
+            self.device.set_object_name(
+                command_buffer,
+                &format!("upload batch {} command buffer", self.next_batch),
+            );
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            unsafe {
+                self.device
+                    .begin_command_buffer(command_buffer, &begin_info)
+                    .unwrap();
+            }
+
+            self.recording = Some(Batch {
+                id: self.next_batch,
+                command_buffer,
+                timeline_value: 0,
+                staging_buffers: Vec::new(),
+            });
+            self.next_batch += 1;
+        }
+        self.recording.as_mut().unwrap()
+    }
+
+    /// Submits the copies recorded since the last call, if any. Meant to be called once per
+    /// frame before the frame's own work is submitted to the same queue.
+    pub fn submit(&mut self, queue: vk::Queue, timeline: &mut GpuTimeline) {
+        let mut batch = match self.recording.take() {
+            Some(batch) => batch,
+            None => return,
+        };
+
+        // Make the copies visible to any vertex input that is submitted after this batch.
+        let memory_barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ)
+            .build();
+        let memory_barriers = [memory_barrier];
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                batch.command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::DependencyFlags::empty(),
+                &memory_barriers,
+                &[],
+                &[],
+            );
+            self.device
+                .end_command_buffer(batch.command_buffer)
+                .unwrap();
+        }
+
+        let command_buffers = [batch.command_buffer];
+        let submission = Submission {
+            command_buffers: &command_buffers,
+            ..Default::default()
+        };
+        batch.timeline_value = timeline.submit(queue, &submission);
+
+        log::debug!(target: "vkrs",
+                    "Submitted upload batch {} with {} copies",
+                    batch.id,
+                    batch.staging_buffers.len());
+        self.in_flight.push_back(batch);
+    }
+
+    /// Retires every submitted batch that has finished executing and releases its staging
+    /// memory. Batches are retired in submission order.
+    pub fn poll(&mut self, timeline: &mut GpuTimeline) {
+        let completed_value = timeline.completed_value();
+        while let Some(batch) = self.in_flight.front() {
+            if batch.timeline_value > completed_value {
+                break;
+            }
+            let batch = self.in_flight.pop_front().unwrap();
+            self.free_batch(batch);
+        }
+    }
+
+    pub fn is_ready(&self, handle: UploadHandle) -> bool {
+        match &self.recording {
+            Some(batch) => handle.batch < batch.id,
+            None => true,
+        }
+    }
+
+    /// The staging buffers are released when the batch is dropped.
+    fn free_batch(&self, batch: Batch) {
+        unsafe {
+            self.device
+                .free_command_buffers(self.command_pool.handle(), &[batch.command_buffer]);
+        }
+    }
+}
+
+impl Drop for UploadManager {
+    /// The device must be idle when the upload manager is dropped.
+    fn drop(&mut self) {
+        if let Some(batch) = self.recording.take() {
+            self.free_batch(batch);
+        }
+        while let Some(batch) = self.in_flight.pop_front() {
+            self.free_batch(batch);
+        }
+    }
+}
@@ -1,28 +1,114 @@
-use std::{ffi::CStr, os::raw::c_char};
+use ash::vk;
 
-#[cfg(debug_assertions)]
-pub const ENABLE_VALIDATION_LAYERS: bool = true;
-
-#[cfg(not(debug_assertions))]
-pub const ENABLE_VALIDATION_LAYERS: bool = false;
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    ptr,
+};
 
 const REQUIRED_VALIDATION_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation\0"];
 
-pub fn get_validation_layer_names_as_ptrs() -> Vec<*const c_char> {
-    if ENABLE_VALIDATION_LAYERS {
-        REQUIRED_VALIDATION_LAYERS
-            .iter()
-            .map(|layer| {
-                CStr::from_bytes_with_nul(layer.as_bytes())
-                    .unwrap()
-                    .as_ptr()
-            })
+/// Whether to enable the validation layers, and which of their optional features to enable
+/// through `VK_EXT_validation_features`. Validation is enabled by default in debug builds only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationConfig {
+    pub enabled: bool,
+    pub gpu_assisted: bool,
+    pub best_practices: bool,
+    pub synchronization: bool,
+    pub debug_printf: bool,
+    /// Exit with a failure status if any validation error was emitted, for integration tests.
+    /// Renderers collect the errors in [`crate::Renderer::validation_collector`].
+    pub fail_on_error: bool,
+    /// Message ID names, or ID numbers in hexadecimal, of messages not to report.
+    pub suppressed_message_ids: Vec<String>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            gpu_assisted: false,
+            best_practices: false,
+            synchronization: false,
+            debug_printf: false,
+            fail_on_error: false,
+            suppressed_message_ids: Vec::new(),
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// Parses `off`, `on`, or a comma separated list of the features `gpu-assisted`,
+    /// `best-practices`, `sync`, `debug-printf` and `fail-on-error`, which enable validation with
+    /// those features. Suppressed message IDs are set separately.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut config = Self {
+            enabled: true,
+            ..Self::default()
+        };
+        if value == "off" {
+            config.enabled = false;
+            return Ok(config);
+        }
+        for feature in value.split(',').map(str::trim) {
+            match feature {
+                "on" => {}
+                "gpu-assisted" => config.gpu_assisted = true,
+                "best-practices" => config.best_practices = true,
+                "sync" => config.synchronization = true,
+                "debug-printf" => config.debug_printf = true,
+                "fail-on-error" => config.fail_on_error = true,
+                _ => return Err(format!("Unknown validation feature {:?}", feature)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Parses a comma separated list of message IDs to suppress.
+    pub fn parse_message_ids(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::to_owned)
             .collect()
-    } else {
-        Vec::new()
+    }
+
+    /// The validation features to enable, if validation is enabled.
+    pub fn feature_enables(&self) -> Vec<vk::ValidationFeatureEnableEXT> {
+        let mut enables = Vec::new();
+        if !self.enabled {
+            return enables;
+        }
+        if self.gpu_assisted {
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            enables.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if self.best_practices {
+            enables.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+        }
+        if self.synchronization {
+            enables.push(vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION);
+        }
+        if self.debug_printf {
+            enables.push(vk::ValidationFeatureEnableEXT::DEBUG_PRINTF);
+        }
+        enables
     }
 }
 
+pub fn get_validation_layer_names_as_ptrs() -> Vec<*const c_char> {
+    REQUIRED_VALIDATION_LAYERS
+        .iter()
+        .map(|layer| {
+            CStr::from_bytes_with_nul(layer.as_bytes())
+                .unwrap()
+                .as_ptr()
+        })
+        .collect()
+}
+
 pub fn check_validation_layer_support(entry: &ash::Entry) -> Result<(), String> {
     let available_validation_layers = entry
         .enumerate_instance_layer_properties()
@@ -64,3 +150,194 @@ pub fn check_validation_layer_support(entry: &ash::Entry) -> Result<(), String>
         Err(missing_layers)
     }
 }
+
+/// Returns the instance extensions provided by the validation layers, such as
+/// `VK_EXT_validation_features`, which are only reported when asked for by layer name.
+pub fn get_validation_layer_extensions(entry: &ash::Entry) -> Vec<CString> {
+    let mut extensions = Vec::new();
+    for layer in get_validation_layer_names_as_ptrs() {
+        let properties = unsafe {
+            let mut count = 0;
+            entry
+                .fp_v1_0()
+                .enumerate_instance_extension_properties(layer, &mut count, ptr::null_mut())
+                .result()
+                .expect("Failed to enumerate validation layer extensions.");
+            let mut properties = Vec::with_capacity(count as usize);
+            entry
+                .fp_v1_0()
+                .enumerate_instance_extension_properties(layer, &mut count, properties.as_mut_ptr())
+                .result()
+                .expect("Failed to enumerate validation layer extensions.");
+            properties.set_len(count as usize);
+            properties
+        };
+        extensions.extend(
+            properties
+                .iter()
+                .map(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) }.to_owned()),
+        );
+    }
+    extensions
+}
+
+/// Makes the validation layer include where a `debugPrintfEXT` message was printed from, which is
+/// where the pipeline and shader stage are parsed from. Settings already in the environment take
+/// precedence.
+pub fn configure_debug_printf(validation: &ValidationConfig) {
+    if validation.enabled
+        && validation.debug_printf
+        && std::env::var_os("VK_LAYER_PRINTF_VERBOSE").is_none()
+    {
+        std::env::set_var("VK_LAYER_PRINTF_VERBOSE", "1");
+    }
+}
+
+/// A `debugPrintfEXT` message from a shader, parsed from the validation layer message.
+pub struct DebugPrintfMessage<'a> {
+    pub pipeline: Option<&'a str>,
+    pub stage: Option<&'a str>,
+    pub message: &'a str,
+}
+
+impl<'a> DebugPrintfMessage<'a> {
+    /// Whether the message with the ID `message_id_name` comes from `debugPrintfEXT`.
+    pub fn is_debug_printf(message_id_name: &str) -> bool {
+        message_id_name.ends_with("DEBUG-PRINTF")
+    }
+
+    /// The layer formats messages as `<header> | MessageID = <id> | <details>`, where verbose
+    /// details describe where the message was printed from, followed by the message itself.
+    pub fn parse(message: &'a str) -> Self {
+        let details = message.rsplit(" | ").next().unwrap_or(message);
+        let pipeline = Self::field(details, "Pipeline ");
+        let stage = Self::field(details, "Stage = ");
+        let message = match stage {
+            Some(_) => details
+                .rsplit_once("\n\n")
+                .or_else(|| details.rsplit_once('\n'))
+                .map_or(details, |(_, message)| message),
+            None => details,
+        };
+        Self {
+            pipeline,
+            stage,
+            message: message.trim(),
+        }
+    }
+
+    /// Returns the value after `key`, which is either enclosed in parentheses or ends at the next
+    /// whitespace.
+    fn field(details: &'a str, key: &str) -> Option<&'a str> {
+        let value = &details[details.find(key)? + key.len()..];
+        let value = match value.strip_prefix('(') {
+            Some(value) => &value[..value.find(')')?],
+            None => value
+                .split_whitespace()
+                .next()?
+                .trim_end_matches(['.', ',']),
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_validation_off_and_on() {
+        assert!(!ValidationConfig::parse("off").unwrap().enabled);
+        assert_eq!(
+            ValidationConfig::parse("on").unwrap(),
+            ValidationConfig {
+                enabled: true,
+                ..ValidationConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_validation_features() {
+        let config = ValidationConfig::parse("gpu-assisted, sync,debug-printf").unwrap();
+        assert!(config.enabled);
+        assert!(config.gpu_assisted);
+        assert!(!config.best_practices);
+        assert!(config.synchronization);
+        assert!(config.debug_printf);
+        assert!(!config.fail_on_error);
+
+        let config = ValidationConfig::parse("best-practices,fail-on-error").unwrap();
+        assert!(config.best_practices);
+        assert!(config.fail_on_error);
+    }
+
+    #[test]
+    fn rejects_unknown_validation_features() {
+        assert!(ValidationConfig::parse("gpu-assisted,shader-printf").is_err());
+        assert!(ValidationConfig::parse("").is_err());
+        // `off` is not a feature and cannot be combined with any.
+        assert!(ValidationConfig::parse("off,sync").is_err());
+    }
+
+    #[test]
+    fn parses_message_ids() {
+        assert_eq!(
+            ValidationConfig::parse_message_ids("VUID-vkCmdDraw-None-02699, 0x9981c31b,"),
+            ["VUID-vkCmdDraw-None-02699", "0x9981c31b"]
+        );
+        assert!(ValidationConfig::parse_message_ids("").is_empty());
+    }
+
+    #[test]
+    fn only_enables_features_with_validation() {
+        let mut config = ValidationConfig::parse("gpu-assisted,debug-printf").unwrap();
+        assert_eq!(
+            config.feature_enables(),
+            [
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+                vk::ValidationFeatureEnableEXT::DEBUG_PRINTF,
+            ]
+        );
+        config.enabled = false;
+        assert!(config.feature_enables().is_empty());
+    }
+
+    #[test]
+    fn detects_debug_printf_messages() {
+        assert!(DebugPrintfMessage::is_debug_printf(
+            "UNASSIGNED-DEBUG-PRINTF"
+        ));
+        assert!(DebugPrintfMessage::is_debug_printf("WARNING-DEBUG-PRINTF"));
+        assert!(!DebugPrintfMessage::is_debug_printf(
+            "VUID-vkCmdDraw-None-02699"
+        ));
+    }
+
+    #[test]
+    fn parses_verbose_debug_printf_message() {
+        let message = "Validation Information: [ UNASSIGNED-DEBUG-PRINTF ] Object 0: handle = \
+            0x5596d1b0e430, type = VK_OBJECT_TYPE_QUEUE; | MessageID = 0x92394c89 | Command \
+            buffer (0x5596d1c3f5a0). Draw Index 0x0. Pipeline (0x967dd1000000000e). Shader \
+            Module (0xcb3ee80000000007). Shader Instruction Index = 95. Stage = Fragment.  \
+            Fragment coord (x,y) = (400.5, 300.5). Debug shader printf message generated at \
+            line 21.\n\n21:     debugPrintfEXT(\"color %v3f\", fragColor);\n\ncolor 1.000000, \
+            0.500000, 0.000000";
+        let printf = DebugPrintfMessage::parse(message);
+        assert_eq!(printf.pipeline, Some("0x967dd1000000000e"));
+        assert_eq!(printf.stage, Some("Fragment"));
+        assert_eq!(printf.message, "color 1.000000, 0.500000, 0.000000");
+    }
+
+    #[test]
+    fn parses_debug_printf_message() {
+        let message = "Validation Information: [ UNASSIGNED-DEBUG-PRINTF ] Object 0: handle = \
+            0x5596d1b0e430, type = VK_OBJECT_TYPE_QUEUE; | MessageID = 0x92394c89 | color \
+            1.000000, 0.500000, 0.000000";
+        let printf = DebugPrintfMessage::parse(message);
+        assert_eq!(printf.pipeline, None);
+        assert_eq!(printf.stage, None);
+        assert_eq!(printf.message, "color 1.000000, 0.500000, 0.000000");
+    }
+}
@@ -0,0 +1,439 @@
+//! Where messages from the validation layers go.
+//!
+//! The debug messenger hands every message to a [`ValidationReporter`], which drops suppressed
+//! and repeated messages before passing the rest on to its sinks. Messages are always logged, and
+//! more sinks can be added, such as a [`ValidationCollector`] to fail tests on validation errors.
+
+use super::validation::DebugPrintfMessage;
+
+use ash::vk;
+
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    os::raw::c_char,
+    slice,
+    sync::{Arc, Mutex},
+};
+
+/// An object a validation message is about.
+#[derive(Clone, Debug)]
+pub struct ValidationObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    pub name: Option<String>,
+}
+
+/// A message from the validation layers with the details from
+/// `VkDebugUtilsMessengerCallbackDataEXT`.
+#[derive(Clone, Debug)]
+pub struct ValidationMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub types: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub id_name: String,
+    pub id_number: i32,
+    pub message: String,
+    pub objects: Vec<ValidationObject>,
+    pub queue_labels: Vec<String>,
+    pub command_buffer_labels: Vec<String>,
+}
+
+impl ValidationMessage {
+    /// # Safety
+    ///
+    /// `data` must be the callback data passed to a debug messenger callback.
+    pub(crate) unsafe fn from_callback_data(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        types: vk::DebugUtilsMessageTypeFlagsEXT,
+        data: &vk::DebugUtilsMessengerCallbackDataEXT,
+    ) -> Self {
+        let objects = array(data.p_objects, data.object_count)
+            .iter()
+            .map(|object| ValidationObject {
+                object_type: object.object_type,
+                handle: object.object_handle,
+                name: optional_string(object.p_object_name),
+            })
+            .collect();
+        let labels = |labels: &[vk::DebugUtilsLabelEXT]| {
+            labels
+                .iter()
+                .filter_map(|label| optional_string(label.p_label_name))
+                .collect()
+        };
+        Self {
+            severity,
+            types,
+            id_name: optional_string(data.p_message_id_name).unwrap_or_default(),
+            id_number: data.message_id_number,
+            message: optional_string(data.p_message).unwrap_or_default(),
+            objects,
+            queue_labels: labels(array(data.p_queue_labels, data.queue_label_count)),
+            command_buffer_labels: labels(array(data.p_cmd_buf_labels, data.cmd_buf_label_count)),
+        }
+    }
+
+    /// Describes the objects and the labels the message was emitted in, or returns an empty
+    /// string if there are none.
+    fn context(&self) -> String {
+        let mut context = String::new();
+        let objects = self
+            .objects
+            .iter()
+            .map(|object| match &object.name {
+                Some(name) => format!("{:?} {:?}", object.object_type, name),
+                None => format!("{:?} {:#x}", object.object_type, object.handle),
+            })
+            .collect::<Vec<_>>();
+        if !objects.is_empty() {
+            context.push_str(&format!("\n    objects: {}", objects.join(", ")));
+        }
+        if !self.queue_labels.is_empty() {
+            context.push_str(&format!("\n    queue: {}", self.queue_labels.join(" > ")));
+        }
+        if !self.command_buffer_labels.is_empty() {
+            context.push_str(&format!(
+                "\n    command buffer: {}",
+                self.command_buffer_labels.join(" > ")
+            ));
+        }
+        context
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity
+            .contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR)
+    }
+
+    /// Whether `id` is the name of the message ID, or its number in hexadecimal.
+    pub fn has_id(&self, id: &str) -> bool {
+        self.id_name == id
+            || id
+                .strip_prefix("0x")
+                .and_then(|number| u32::from_str_radix(number, 16).ok())
+                .is_some_and(|number| number as i32 == self.id_number)
+    }
+}
+
+unsafe fn optional_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+unsafe fn array<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() || count == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
+/// Receives validation messages. Messages may be reported from any thread the application makes
+/// Vulkan calls on.
+pub trait ValidationSink: Send {
+    fn receive(&mut self, message: &ValidationMessage);
+}
+
+/// Logs messages to the `vulkan` target, and shader printf messages to the `shader` target.
+pub struct LogSink;
+
+impl ValidationSink for LogSink {
+    fn receive(&mut self, message: &ValidationMessage) {
+        use vk::DebugUtilsMessageSeverityFlagsEXT as SeverityFlags;
+        use vk::DebugUtilsMessageTypeFlagsEXT as TypeFlags;
+
+        if DebugPrintfMessage::is_debug_printf(&message.id_name) {
+            let printf = DebugPrintfMessage::parse(&message.message);
+            log::info!(target: "shader",
+                       "[pipeline {}, {} stage] {}",
+                       printf.pipeline.unwrap_or("unknown"),
+                       printf.stage.unwrap_or("unknown"),
+                       printf.message);
+            return;
+        }
+
+        let message_types = match message.types {
+            TypeFlags::GENERAL => "[General]",
+            TypeFlags::VALIDATION => "[Validation]",
+            TypeFlags::PERFORMANCE => "[Performance]",
+            _ => "[Unknown]",
+        };
+        let context = message.context();
+        match message.severity {
+            SeverityFlags::VERBOSE => {
+                log::debug!(target: "vulkan", "{}: {}{}", message_types, message.message, context);
+            }
+            SeverityFlags::INFO => {
+                log::info!(target: "vulkan", "{}: {}{}", message_types, message.message, context)
+            }
+            SeverityFlags::WARNING => {
+                log::warn!(target: "vulkan", "{}: {}{}", message_types, message.message, context)
+            }
+            SeverityFlags::ERROR => {
+                log::error!(target: "vulkan", "{}: {}{}", message_types, message.message, context)
+            }
+            _ => {
+                log::error!(target: "vulkan", "Unknown severity {}", message.severity.as_raw());
+                log::error!(target: "vulkan", "{}: {}{}", message_types, message.message, context);
+            }
+        };
+    }
+}
+
+/// Collects validation errors, so that integration tests can fail if any were emitted. Clones
+/// share the same errors.
+#[derive(Clone, Default)]
+pub struct ValidationCollector {
+    errors: Arc<Mutex<Vec<ValidationMessage>>>,
+}
+
+impl ValidationCollector {
+    pub fn errors(&self) -> Vec<ValidationMessage> {
+        self.errors.lock().unwrap().clone()
+    }
+}
+
+impl ValidationSink for ValidationCollector {
+    fn receive(&mut self, message: &ValidationMessage) {
+        if message.is_error() {
+            self.errors.lock().unwrap().push(message.clone());
+        }
+    }
+}
+
+/// Passes messages on to the sinks, except for suppressed messages and repeats of messages that
+/// have already been reported. Repeats are counted instead, and the counts are logged as they
+/// grow and when the reporter is dropped.
+pub struct ValidationReporter {
+    suppressed_message_ids: Vec<String>,
+    state: Mutex<ReporterState>,
+}
+
+struct ReporterState {
+    sinks: Vec<Box<dyn ValidationSink>>,
+    // Keyed by message ID and text, since the same ID is reported for different objects.
+    counts: HashMap<(i32, String), u64>,
+}
+
+impl ValidationReporter {
+    /// `suppressed_message_ids` are message ID names, or ID numbers in hexadecimal.
+    pub fn new(suppressed_message_ids: Vec<String>) -> Self {
+        Self {
+            suppressed_message_ids,
+            state: Mutex::new(ReporterState {
+                sinks: vec![Box::new(LogSink)],
+                counts: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn add_sink(&self, sink: Box<dyn ValidationSink>) {
+        self.state.lock().unwrap().sinks.push(sink);
+    }
+
+    pub fn report(&self, message: &ValidationMessage) {
+        if self
+            .suppressed_message_ids
+            .iter()
+            .any(|id| message.has_id(id))
+        {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // Every shader printf is reported, identical values are expected across frames.
+        if !DebugPrintfMessage::is_debug_printf(&message.id_name) {
+            let count = state
+                .counts
+                .entry((message.id_number, message.message.clone()))
+                .or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                if is_power_of_ten(*count) {
+                    log::warn!(target: "vulkan",
+                               "{} repeated {} times",
+                               message.id_name,
+                               count);
+                }
+                return;
+            }
+        }
+        for sink in &mut state.sinks {
+            sink.receive(message);
+        }
+    }
+}
+
+fn is_power_of_ten(mut value: u64) -> bool {
+    while value >= 10 && value % 10 == 0 {
+        value /= 10;
+    }
+    value == 1
+}
+
+impl Drop for ValidationReporter {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap();
+        for ((_, message), count) in &state.counts {
+            if *count > 1 {
+                log::info!(target: "vulkan", "Repeated {} times: {}", count, message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{ffi::CString, ptr};
+
+    const ID_NAME: &str = "VUID-vkCmdDraw-None-02699";
+    // Does not fit in an i32, like about half of the IDs the layers hash their names to.
+    const ID_NUMBER: u32 = 0x9981c31b;
+    const MESSAGE: &str = "Validation Error: [ VUID-vkCmdDraw-None-02699 ] Object 0: handle = \
+        0x6b000000006b, name = descriptor set, type = VK_OBJECT_TYPE_DESCRIPTOR_SET; | MessageID \
+        = 0x9981c31b | Descriptor set VkDescriptorSet 0x6b000000006b[descriptor set] encountered \
+        the following validation error at vkCmdDraw time: Descriptor in binding #0 index 0 is \
+        being used in draw but has never been updated via vkUpdateDescriptorSets() or a similar \
+        call.";
+
+    fn message(severity: vk::DebugUtilsMessageSeverityFlagsEXT, text: &str) -> ValidationMessage {
+        let id_name = CString::new(ID_NAME).unwrap();
+        let text = CString::new(text).unwrap();
+        let object_name = CString::new("descriptor set").unwrap();
+        let label = CString::new("scene").unwrap();
+        let objects = [vk::DebugUtilsObjectNameInfoEXT {
+            object_type: vk::ObjectType::DESCRIPTOR_SET,
+            object_handle: 0x6b000000006b,
+            p_object_name: object_name.as_ptr(),
+            ..Default::default()
+        }];
+        let labels = [vk::DebugUtilsLabelEXT {
+            p_label_name: label.as_ptr(),
+            ..Default::default()
+        }];
+        let data = vk::DebugUtilsMessengerCallbackDataEXT {
+            p_message_id_name: id_name.as_ptr(),
+            message_id_number: ID_NUMBER as i32,
+            p_message: text.as_ptr(),
+            queue_label_count: 0,
+            p_queue_labels: ptr::null(),
+            cmd_buf_label_count: labels.len() as u32,
+            p_cmd_buf_labels: labels.as_ptr(),
+            object_count: objects.len() as u32,
+            p_objects: objects.as_ptr(),
+            ..Default::default()
+        };
+        unsafe {
+            ValidationMessage::from_callback_data(
+                severity,
+                vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+                &data,
+            )
+        }
+    }
+
+    fn error(text: &str) -> ValidationMessage {
+        message(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR, text)
+    }
+
+    fn reporter_with_collector(
+        suppressed_message_ids: &[&str],
+    ) -> (ValidationReporter, ValidationCollector) {
+        let reporter = ValidationReporter::new(
+            suppressed_message_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+        );
+        let collector = ValidationCollector::default();
+        reporter.add_sink(Box::new(collector.clone()));
+        (reporter, collector)
+    }
+
+    #[test]
+    fn reads_callback_data() {
+        let message = error(MESSAGE);
+        assert_eq!(message.id_name, ID_NAME);
+        assert_eq!(message.id_number, ID_NUMBER as i32);
+        assert_eq!(message.message, MESSAGE);
+        assert_eq!(message.objects.len(), 1);
+        assert_eq!(
+            message.objects[0].object_type,
+            vk::ObjectType::DESCRIPTOR_SET
+        );
+        assert_eq!(message.objects[0].name.as_deref(), Some("descriptor set"));
+        assert!(message.queue_labels.is_empty());
+        assert_eq!(message.command_buffer_labels, ["scene"]);
+        assert!(message.is_error());
+    }
+
+    #[test]
+    fn collector_keeps_only_errors() {
+        let (reporter, collector) = reporter_with_collector(&[]);
+        reporter.report(&message(
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            "a warning",
+        ));
+        reporter.report(&error(MESSAGE));
+        let errors = collector.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, MESSAGE);
+    }
+
+    #[test]
+    fn counts_repeats_and_reports_them_once() {
+        let (reporter, collector) = reporter_with_collector(&[]);
+        for _ in 0..3 {
+            reporter.report(&error(MESSAGE));
+        }
+        // The same ID with another text, such as for another object, is a different message.
+        reporter.report(&error("another descriptor set"));
+
+        assert_eq!(collector.errors().len(), 2);
+        let state = reporter.state.lock().unwrap();
+        assert_eq!(state.counts[&(ID_NUMBER as i32, MESSAGE.to_owned())], 3);
+        assert_eq!(
+            state.counts[&(ID_NUMBER as i32, "another descriptor set".to_owned())],
+            1
+        );
+    }
+
+    #[test]
+    fn suppresses_by_id_name() {
+        let (reporter, collector) = reporter_with_collector(&[ID_NAME]);
+        reporter.report(&error(MESSAGE));
+        assert!(collector.errors().is_empty());
+        assert!(reporter.state.lock().unwrap().counts.is_empty());
+    }
+
+    #[test]
+    fn suppresses_by_id_number() {
+        let (reporter, collector) = reporter_with_collector(&["0x9981c31b"]);
+        reporter.report(&error(MESSAGE));
+        assert!(collector.errors().is_empty());
+    }
+
+    #[test]
+    fn does_not_suppress_other_ids() {
+        let (reporter, collector) =
+            reporter_with_collector(&["VUID-vkCmdDraw-None-02700", "0x9981c31c", "9981c31b"]);
+        reporter.report(&error(MESSAGE));
+        assert_eq!(collector.errors().len(), 1);
+    }
+
+    #[test]
+    fn logs_repeats_at_powers_of_ten() {
+        let logged = (1..=1000)
+            .filter(|count| is_power_of_ten(*count))
+            .collect::<Vec<_>>();
+        assert_eq!(logged, [1, 10, 100, 1000]);
+        assert!(!is_power_of_ten(0));
+        assert!(!is_power_of_ten(20));
+        assert!(!is_power_of_ten(101));
+    }
+}
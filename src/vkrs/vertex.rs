@@ -1,12 +1,16 @@
-use glam::{Vec2, Vec3};
+use glam::Vec3;
 use memoffset::offset_of;
 use std::mem::size_of;
 
 use ash::vk;
 
-#[derive(Clone, Copy)]
+/// A vertex of a mesh, see [`Renderer::add_mesh`](super::Renderer::add_mesh).
+#[derive(Clone, Copy, Debug)]
 pub struct Vertex {
-    pub pos: Vec2,
+    pub pos: Vec3,
+    /// Points away from the front face, which is the counter-clockwise one.
+    pub normal: Vec3,
+    /// Multiplied with the base color of the material.
     pub color: Vec3,
 }
 
@@ -19,19 +23,25 @@ impl Vertex {
             .build()
     }
 
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
         let position_desc = vk::VertexInputAttributeDescription::builder()
             .binding(0)
             .location(0)
-            .format(vk::Format::R32G32_SFLOAT)
+            .format(vk::Format::R32G32B32_SFLOAT)
             .offset(offset_of!(Vertex, pos) as _)
             .build();
-        let color_desc = vk::VertexInputAttributeDescription::builder()
+        let normal_desc = vk::VertexInputAttributeDescription::builder()
             .binding(0)
             .location(1)
             .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Vertex, normal) as _)
+            .build();
+        let color_desc = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(2)
+            .format(vk::Format::R32G32B32_SFLOAT)
             .offset(offset_of!(Vertex, color) as _)
             .build();
-        [position_desc, color_desc]
+        [position_desc, normal_desc, color_desc]
     }
 }
@@ -1,26 +1,40 @@
+use super::capabilities::Capabilities;
+use super::device_features::{DeviceFeatures, DeviceRequirements};
+use super::device_selector::{format_uuid, DeviceSelector};
 use super::extensions;
+use super::frame_stats::DrawStats;
+use super::handles::{
+    Buffer, CommandPool, DebugMessenger, DescriptorPool, DescriptorSetLayout, Device, Framebuffer,
+    Image, ImageView, Instance, Pipeline, PipelineLayout, RenderPass, Semaphore, Surface,
+};
 use super::queue_family_indices::QueueFamilyIndices;
 use super::shader;
 use super::swapchain;
-use super::uniform_buffer_object::UniformBufferObject;
-use super::validation;
+use super::uniform_buffer_object::{ObjectPushConstants, UniformBufferObject};
+use super::validation::{self, ValidationConfig};
+use super::validation_sink::{LogSink, ValidationMessage, ValidationReporter, ValidationSink};
 use super::vertex::Vertex;
 
 use ash::vk;
+use raw_window_handle::HasRawWindowHandle;
 
 use std::{
     ffi::{CStr, CString},
-    mem::{align_of, size_of},
+    mem::size_of,
     os::raw::{c_char, c_void},
-    path::PathBuf,
+    rc::Rc,
+    slice,
 };
 
+/// Creates the instance, with the validation layers and features in `validation` if they are
+/// enabled, and the surface extensions for `window` if there is one.
 pub fn create_instance(
     name: &str,
     version: u32,
-    entry: &ash::Entry,
-    window: &winit::window::Window,
-) -> ash::Instance {
+    entry: ash::Entry,
+    window: Option<&dyn HasRawWindowHandle>,
+    validation: &ValidationConfig,
+) -> Rc<Instance> {
     let name = CString::new(name).unwrap();
 
     let app_info = vk::ApplicationInfo::builder()
@@ -30,201 +44,353 @@ pub fn create_instance(
         .engine_version(version)
         .api_version(vk::API_VERSION_1_2);
 
-    let required_extensions = extensions::get_required_extensions(window);
-    if let Err(missing_extensions) =
-        extensions::check_required_extensions(entry, &required_extensions)
-    {
-        panic!("Missing extensions: {}", missing_extensions)
+    if validation.enabled {
+        if let Err(missing_layers) = validation::check_validation_layer_support(&entry) {
+            panic!(
+                "Missing validation layers: {}, run with --validation off to disable validation",
+                missing_layers
+            );
+        }
+    }
+
+    let mut available_extensions = extensions::get_available_instance_extensions(&entry);
+    if validation.enabled {
+        available_extensions.extend(validation::get_validation_layer_extensions(&entry));
     }
+    let available_extensions = available_extensions
+        .iter()
+        .map(|ext| ext.as_c_str())
+        .collect::<Vec<_>>();
+    let enabled_extensions = extensions::get_instance_extension_requests(window, validation)
+        .negotiate(&available_extensions)
+        .unwrap_or_else(|missing| {
+            panic!("Missing extensions: {}", extensions::join_names(&missing))
+        });
+    log::debug!(target: "vkrs",
+                "Enabling instance extensions {}",
+                extensions::join_names(enabled_extensions.names()));
 
     let validation_layer_names = validation::get_validation_layer_names_as_ptrs();
-    let instance_extensions: Vec<*const c_char> =
-        required_extensions.iter().map(|ext| ext.as_ptr()).collect();
+    let instance_extensions: Vec<*const c_char> = enabled_extensions
+        .names()
+        .iter()
+        .map(|ext| ext.as_ptr())
+        .collect();
     let mut instance_create_info = vk::InstanceCreateInfo::builder()
         .application_info(&app_info)
         .enabled_extension_names(&instance_extensions);
 
     // Used to debug create_instance and destroy_instance.
-    let mut debug_utils_create_info = populate_debug_messenger_create_info();
-    if validation::ENABLE_VALIDATION_LAYERS {
-        if let Err(missing_layers) = validation::check_validation_layer_support(entry) {
-            panic!("Missing validation layers: {}", missing_layers);
-        }
+    let mut debug_utils_create_info = populate_debug_messenger_create_info(validation);
+    if validation.enabled {
         instance_create_info = instance_create_info
             .enabled_layer_names(&validation_layer_names)
             .push_next(&mut debug_utils_create_info);
     }
 
-    unsafe {
+    let mut validation = validation.clone();
+    let validation_feature_enables = validation.feature_enables();
+    let mut validation_features = vk::ValidationFeaturesEXT::builder()
+        .enabled_validation_features(&validation_feature_enables);
+    if !validation_feature_enables.is_empty() {
+        if enabled_extensions.contains(vk::ExtValidationFeaturesFn::name()) {
+            log::info!(target: "vkrs",
+                       "Enabling validation features {:?}",
+                       validation_feature_enables);
+            instance_create_info = instance_create_info.push_next(&mut validation_features);
+        } else {
+            log::warn!(target: "vkrs",
+                       "VK_EXT_validation_features is not available, validation features are disabled");
+            validation = ValidationConfig {
+                enabled: true,
+                fail_on_error: validation.fail_on_error,
+                suppressed_message_ids: validation.suppressed_message_ids,
+                ..Default::default()
+            };
+        }
+    }
+
+    validation::configure_debug_printf(&validation);
+    let instance = unsafe {
         entry
             .create_instance(&instance_create_info, None)
             .expect("Failed to create Vulkan instance.")
-    }
+    };
+    Instance::new(entry, instance, enabled_extensions, validation)
 }
 
+/// Hands messages to the `ValidationReporter` in `p_user_data`. Messages about creating and
+/// destroying the instance have no reporter and are only logged.
 unsafe extern "system" fn debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_types: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut c_void,
+    p_user_data: *mut c_void,
 ) -> vk::Bool32 {
-    use vk::DebugUtilsMessageSeverityFlagsEXT as SeverityFlags;
-    use vk::DebugUtilsMessageTypeFlagsEXT as TypeFlags;
-
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    let message_types = match message_types {
-        TypeFlags::GENERAL => "[General]",
-        TypeFlags::VALIDATION => "[Validation]",
-        TypeFlags::PERFORMANCE => "[Performance]",
-        _ => "[Unknown]",
-    };
-    match message_severity {
-        SeverityFlags::VERBOSE => {
-            log::debug!(target: "vulkan", "{}: {:?}", message_types, message);
-        }
-        SeverityFlags::INFO => {
-            log::info!(target: "vulkan", "{}: {:?}", message_types, message)
-        }
-        SeverityFlags::WARNING => {
-            log::warn!(target: "vulkan", "{}: {:?}", message_types, message)
-        }
-        SeverityFlags::ERROR => {
-            log::error!(target: "vulkan", "{}: {:?}", message_types, message)
-        }
-        _ => {
-            log::error!(target: "vulkan", "Unknown severity {}", message_severity.as_raw());
-            log::error!(target: "vulkan", "{}: {:?}", message_types, message);
-        }
-    };
+    let message =
+        ValidationMessage::from_callback_data(message_severity, message_types, &*p_callback_data);
+    match (p_user_data as *const ValidationReporter).as_ref() {
+        Some(reporter) => reporter.report(&message),
+        None => LogSink.receive(&message),
+    }
     vk::FALSE
 }
 
-fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT {
+fn populate_debug_messenger_create_info(
+    validation: &ValidationConfig,
+) -> vk::DebugUtilsMessengerCreateInfoEXT {
     use vk::DebugUtilsMessageSeverityFlagsEXT as SeverityFlags;
     use vk::DebugUtilsMessageTypeFlagsEXT as TypeFlags;
 
+    let mut message_severity =
+        SeverityFlags::VERBOSE | SeverityFlags::WARNING | SeverityFlags::ERROR;
+    // Shader printf messages are reported as info.
+    if validation.debug_printf {
+        message_severity |= SeverityFlags::INFO;
+    }
     vk::DebugUtilsMessengerCreateInfoEXT::builder()
-        .message_severity(SeverityFlags::VERBOSE | SeverityFlags::WARNING | SeverityFlags::ERROR)
+        .message_severity(message_severity)
         .message_type(TypeFlags::GENERAL | TypeFlags::VALIDATION | TypeFlags::PERFORMANCE)
         .pfn_user_callback(Some(debug_callback))
         .build()
 }
 
-pub fn setup_debug_messenger(
-    entry: &ash::Entry,
-    instance: &ash::Instance,
-) -> (ash::extensions::ext::DebugUtils, vk::DebugUtilsMessengerEXT) {
-    let debug_utils_loader = ash::extensions::ext::DebugUtils::new(entry, instance);
+pub fn setup_debug_messenger(instance: &Rc<Instance>) -> DebugMessenger {
+    let debug_utils_loader = ash::extensions::ext::DebugUtils::new(instance.entry(), instance);
 
-    if !validation::ENABLE_VALIDATION_LAYERS {
-        return (debug_utils_loader, vk::DebugUtilsMessengerEXT::null());
+    if !instance.validation().enabled
+        || !instance
+            .extensions()
+            .contains(ash::extensions::ext::DebugUtils::name())
+    {
+        return DebugMessenger::new(
+            instance,
+            debug_utils_loader,
+            vk::DebugUtilsMessengerEXT::null(),
+        );
     }
 
-    let create_info = populate_debug_messenger_create_info();
+    // Boxed, so that the pointer given to the callback stays valid when the messenger is moved.
+    let reporter = Box::new(ValidationReporter::new(
+        instance.validation().suppressed_message_ids.clone(),
+    ));
+    let mut create_info = populate_debug_messenger_create_info(instance.validation());
+    create_info.p_user_data = &*reporter as *const ValidationReporter as *mut c_void;
 
     let debug_messenger = unsafe {
         debug_utils_loader
             .create_debug_utils_messenger(&create_info, None)
             .expect("Failed to create debug messenger.")
     };
-    (debug_utils_loader, debug_messenger)
+    DebugMessenger::new(instance, debug_utils_loader, debug_messenger).with_reporter(reporter)
 }
 
-fn rate_physical_device(
+/// Returns the score of a suitable device together with the extensions and features to enable on
+/// it, or every reason the device cannot be used. Without a surface, devices are rated for
+/// headless rendering.
+pub fn rate_physical_device(
     instance: &ash::Instance,
-    surface_fn: &ash::extensions::khr::Surface,
-    surface: vk::SurfaceKHR,
+    surface: Option<&Surface>,
     device: vk::PhysicalDevice,
-) -> (u32, Option<QueueFamilyIndices>) {
-    let device_featues = unsafe { instance.get_physical_device_features(device) };
-    if device_featues.geometry_shader != 1 {
-        return (0, None);
-    }
+    requirements: &DeviceRequirements,
+) -> Result<(u32, QueueFamilyIndices, Capabilities), Vec<String>> {
+    let mut rejections = Vec::new();
+
+    let device_properties = unsafe { instance.get_physical_device_properties(device) };
 
-    let indices = QueueFamilyIndices::find_queue_families(instance, surface_fn, surface, device);
-    if !indices.is_complete() {
-        return (0, None);
+    let available_extensions = extensions::get_available_device_extensions(instance, device);
+    let available_extensions = available_extensions
+        .iter()
+        .map(|ext| ext.as_c_str())
+        .collect::<Vec<_>>();
+    let mut wanted_features = DeviceFeatures::default();
+    wanted_features.union(&requirements.required);
+    wanted_features.union(&requirements.optional);
+    let supported_features = DeviceFeatures::query(
+        instance,
+        device,
+        device_properties.api_version,
+        &wanted_features,
+        &available_extensions,
+    );
+    for feature in requirements.required.missing(&supported_features) {
+        rejections.push(format!("missing {}", feature));
     }
 
-    let device_extension_support = extensions::check_device_extension_support(instance, device);
-    if !device_extension_support {
-        return (0, None);
+    let indices = QueueFamilyIndices::find_queue_families(instance, surface, device);
+    if indices.graphics_family.is_none() {
+        rejections.push("no graphics queue".to_owned());
+    }
+    if indices.present_family.is_none() {
+        rejections.push("no queue that can present to the window surface".to_owned());
     }
 
-    // Can only get swapchain support details after we have verified device extension support for it.
-    let swapchain_support_details = swapchain::SupportDetails::new(device, surface_fn, surface);
-    if swapchain_support_details.formats.is_empty()
-        || swapchain_support_details.present_modes.is_empty()
-    {
-        return (0, None);
+    let enabled_extensions = requirements
+        .extension_requests()
+        .negotiate(&available_extensions);
+    if let Err(missing) = &enabled_extensions {
+        rejections.push(format!(
+            "missing device extensions ({})",
+            extensions::join_names(missing)
+        ));
+    } else if let Some(surface) = surface {
+        // Can only get swapchain support details after we have verified device extension support for it.
+        let swapchain_support_details =
+            swapchain::SupportDetails::new(device, surface.loader(), surface.handle());
+        if swapchain_support_details.formats.is_empty() {
+            rejections.push("no surface formats".to_owned());
+        }
+        if swapchain_support_details.present_modes.is_empty() {
+            rejections.push("no present modes".to_owned());
+        }
     }
 
-    let mut score = 0;
-    let device_properties = unsafe { instance.get_physical_device_properties(device) };
+    let enabled_extensions = match enabled_extensions {
+        Ok(enabled_extensions) if rejections.is_empty() => enabled_extensions,
+        _ => return Err(rejections),
+    };
+
+    let mut enabled_features = requirements.optional.intersection(&supported_features);
+    let optional_feature_count = enabled_features.count();
+    enabled_features.union(&requirements.required);
+
+    // Every suitable device scores at least 1, so that it beats an unsuitable one.
+    let mut score = 1;
     if device_properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
         score += 1000;
     } else if device_properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU {
         score += 100;
     }
     score += device_properties.limits.max_image_dimension2_d;
+    score += 10 * optional_feature_count as u32;
+
+    score += 10
+        * requirements
+            .extensions
+            .optional()
+            .iter()
+            .filter(|ext| enabled_extensions.contains(ext))
+            .count() as u32;
 
-    (score, Some(indices))
+    let capabilities = Capabilities {
+        api_version: device_properties.api_version,
+        extensions: enabled_extensions,
+        features: enabled_features,
+    };
+    Ok((score, indices, capabilities))
 }
 
+/// Returns the name and `deviceUUID` of a physical device. Devices older than Vulkan 1.1 have no
+/// UUID and report zeros.
+pub fn physical_device_identity(
+    instance: &ash::Instance,
+    device: vk::PhysicalDevice,
+) -> (String, [u8; 16]) {
+    let properties = unsafe { instance.get_physical_device_properties(device) };
+    let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+    if properties.api_version >= vk::API_VERSION_1_1 {
+        let mut properties2 =
+            vk::PhysicalDeviceProperties2::builder().push_next(&mut id_properties);
+        unsafe { instance.get_physical_device_properties2(device, &mut properties2) };
+    }
+    let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned();
+    (name, id_properties.device_uuid)
+}
+
+/// Picks the device with the highest score, or the device matching `selector` if one is given.
+/// Panics if the selected device is unsuitable, the reasons why every rejected device could not
+/// be used are logged either way. Returns the extensions and features to enable on the device,
+/// which are the required ones and whichever optional ones it supports.
 pub fn select_physical_device(
     instance: &ash::Instance,
-    surface_fn: &ash::extensions::khr::Surface,
-    surface: vk::SurfaceKHR,
-) -> (vk::PhysicalDevice, QueueFamilyIndices) {
+    surface: Option<&Surface>,
+    selector: Option<&DeviceSelector>,
+    requirements: &DeviceRequirements,
+) -> (vk::PhysicalDevice, QueueFamilyIndices, Capabilities) {
     let devices = unsafe {
         instance
             .enumerate_physical_devices()
             .expect("Failed to enumerate physical devices.")
     };
+    let identities = devices
+        .iter()
+        .map(|device| physical_device_identity(instance, *device))
+        .collect::<Vec<_>>();
     log::debug!(target: "vulkan", "Available devices:");
-    devices.iter().for_each(|device| {
-        log::debug!(
-            target: "vulkan",
-            "\t{:?}",
-            unsafe {
-                CStr::from_ptr(instance
-                               .get_physical_device_properties(*device)
-                               .device_name
-                               .as_ptr()
-                ) })
-    });
-
-    let mut best_device_idx = 0;
+    for (idx, (name, uuid)) in identities.iter().enumerate() {
+        log::debug!(target: "vulkan", "\t{}: {:?} ({})", idx, name, format_uuid(uuid));
+    }
+
+    let candidates = match selector {
+        Some(selector) => {
+            let selected = identities
+                .iter()
+                .enumerate()
+                .filter(|(idx, (name, uuid))| selector.matches(*idx, name, uuid))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            if selected.is_empty() {
+                let available = identities
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (name, uuid))| format!("{}: {} ({})", idx, name, format_uuid(uuid)))
+                    .collect::<Vec<_>>();
+                panic!(
+                    "No device matches {}, available devices are: {}",
+                    selector,
+                    available.join(", ")
+                );
+            }
+            log::debug!(target: "vulkan", "Restricting selection to devices matching {}", selector);
+            selected
+        }
+        None => (0..devices.len()).collect(),
+    };
+
+    let mut best_device = None;
     let mut max_score = 0;
-    let mut queue_family_indices = QueueFamilyIndices::new();
-    for (idx, device) in devices.iter().enumerate() {
-        let (score, indices) = rate_physical_device(instance, surface_fn, surface, *device);
-        if score > max_score {
-            if let Some(indices) = indices {
-                best_device_idx = idx;
-                max_score = score;
-                queue_family_indices = indices;
+    let mut rejections = Vec::new();
+    for idx in candidates {
+        let name = &identities[idx].0;
+        match rate_physical_device(instance, surface, devices[idx], requirements) {
+            Ok((score, indices, capabilities)) => {
+                log::debug!(target: "vulkan", "Device {:?} has score {}", name, score);
+                if score > max_score {
+                    max_score = score;
+                    best_device = Some((idx, indices, capabilities));
+                }
+            }
+            Err(reasons) => {
+                log::info!(target: "vulkan",
+                           "Rejected device {:?}: {}",
+                           name,
+                           reasons.join(", "));
+                rejections.push(format!("{:?}: {}", name, reasons.join(", ")));
             }
         }
     }
 
-    if max_score > 0 && queue_family_indices.is_complete() {
-        let best_device = devices[best_device_idx];
-        let properties = unsafe { instance.get_physical_device_properties(best_device) };
+    if let Some((idx, queue_family_indices, capabilities)) = best_device {
         log::debug!(target: "vulkan",
                     "Selected device {:?} with score {}",
-                    unsafe { CStr::from_ptr(properties.device_name.as_ptr()) },
+                    identities[idx].0,
                     max_score);
-        return (best_device, queue_family_indices);
+        return (devices[idx], queue_family_indices, capabilities);
     }
-    panic!("Failed to find a suitable device.");
+    panic!(
+        "Failed to find a suitable device. Rejected devices: {}",
+        rejections.join("; ")
+    );
 }
 
 pub fn create_logical_device_with_graphics_and_present_queue(
-    instance: &ash::Instance,
+    instance: &Rc<Instance>,
     queue_family_indices: &QueueFamilyIndices,
     physical_device: vk::PhysicalDevice,
-) -> (ash::Device, vk::Queue, vk::Queue) {
+    mut capabilities: Capabilities,
+) -> (Rc<Device>, vk::Queue, vk::Queue) {
     let queue_priorities = [1.0f32];
     let graphics_family_index = queue_family_indices.graphics_family.unwrap();
     let present_family_index = queue_family_indices.present_family.unwrap();
@@ -247,99 +413,270 @@ pub fn create_logical_device_with_graphics_and_present_queue(
     };
 
     let required_validation_layers = validation::get_validation_layer_names_as_ptrs();
-    let device_extensions = extensions::get_required_device_extensions();
-    let device_extension_names = device_extensions
+    let device_extension_names = capabilities
+        .extensions
+        .names()
         .iter()
         .map(|ext| ext.as_ptr())
         .collect::<Vec<_>>();
-    let device_features = vk::PhysicalDeviceFeatures::builder();
-    let mut device_create_info = vk::DeviceCreateInfo::builder()
-        .enabled_extension_names(&device_extension_names)
-        .enabled_features(&device_features)
-        .queue_create_infos(&device_queue_create_infos);
-    if validation::ENABLE_VALIDATION_LAYERS {
-        device_create_info = device_create_info.enabled_layer_names(&required_validation_layers);
-    }
 
     // Create the logical device and required queues.
-    let device = unsafe {
-        instance
-            .create_device(physical_device, &device_create_info, None)
-            .expect("Failed to create logical device.")
-    };
+    let api_version = capabilities.api_version;
+    let device = capabilities
+        .features
+        .with_chain(api_version, |device_features| {
+            let mut device_create_info = vk::DeviceCreateInfo::builder()
+                .enabled_extension_names(&device_extension_names)
+                .queue_create_infos(&device_queue_create_infos)
+                .push_next(device_features);
+            if instance.validation().enabled {
+                device_create_info =
+                    device_create_info.enabled_layer_names(&required_validation_layers);
+            }
+            unsafe {
+                instance
+                    .create_device(physical_device, &device_create_info, None)
+                    .expect("Failed to create logical device.")
+            }
+        });
+    log::debug!(target: "vulkan",
+                "Enabled device extensions {} and {} device features",
+                extensions::join_names(capabilities.extensions.names()),
+                capabilities.features.count());
     let graphics_queue = unsafe { device.get_device_queue(graphics_family_index, 0) };
     let present_queue = unsafe { device.get_device_queue(present_family_index, 0) };
-    (device, graphics_queue, present_queue)
+    (
+        Device::new(instance, device, capabilities),
+        graphics_queue,
+        present_queue,
+    )
 }
 
+/// Returns the largest sample count of at most `requested` that `format` supports as a color
+/// attachment, warning if it is less than what was requested.
+pub fn choose_sample_count(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+    requested: u32,
+) -> vk::SampleCountFlags {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let format_properties = unsafe {
+        instance
+            .get_physical_device_image_format_properties(
+                physical_device,
+                format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                vk::ImageCreateFlags::empty(),
+            )
+            .expect("Failed to get image format properties.")
+    };
+    let supported =
+        properties.limits.framebuffer_color_sample_counts & format_properties.sample_counts;
+    let samples = (0..=requested.max(1).ilog2())
+        .rev()
+        .map(|exponent| vk::SampleCountFlags::from_raw(1 << exponent))
+        .find(|samples| supported.contains(*samples))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1);
+    if samples.as_raw() != requested {
+        log::warn!(target: "vkrs",
+                   "{}x MSAA is not supported, using {}x",
+                   requested,
+                   samples.as_raw());
+    }
+    samples
+}
+
+/// Candidates for [`choose_depth_format`], in order of preference. The spec requires that one
+/// of the first two supports being a depth attachment.
+const DEPTH_FORMATS: [vk::Format; 4] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::X8_D24_UNORM_PACK32,
+    vk::Format::D24_UNORM_S8_UINT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+];
+
+/// Returns the most precise depth format that can be used as a depth attachment.
+pub fn choose_depth_format(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::Format {
+    DEPTH_FORMATS
+        .into_iter()
+        .find(|format| {
+            let properties =
+                unsafe { instance.get_physical_device_format_properties(physical_device, *format) };
+            properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("No depth format can be used as a depth attachment.")
+}
+
+/// Creates a render pass with a single color attachment that is cleared and left in
+/// `final_layout`. With more than one sample, the color attachment is multisampled and resolved
+/// into a single sampled attachment that is left in `final_layout` instead. With a
+/// `depth_format`, the pass also has a depth attachment with as many samples as the color
+/// attachment, which is cleared and not stored. The attachments are the color attachment, then
+/// the depth attachment and then the resolve attachment, if there are any.
+///
+/// The dependencies make the pass wait for earlier fragment shader reads of the attachment, and
+/// later fragment shaders wait for it, so that an offscreen image can be rendered to and sampled
+/// from every frame.
 pub fn create_render_pass(
-    device: &ash::Device,
-    swapchain_image_format: vk::Format,
-) -> vk::RenderPass {
+    device: &Rc<Device>,
+    format: vk::Format,
+    final_layout: vk::ImageLayout,
+    samples: vk::SampleCountFlags,
+    depth_format: Option<vk::Format>,
+) -> RenderPass {
+    let multisampled = samples != vk::SampleCountFlags::TYPE_1;
     let color_attachment = vk::AttachmentDescription::builder()
-        .format(swapchain_image_format)
-        .samples(vk::SampleCountFlags::TYPE_1)
+        .format(format)
+        .samples(samples)
         .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(if multisampled {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        })
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(if multisampled {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            final_layout
+        })
+        .build();
+    let resolve_attachment = vk::AttachmentDescription::builder()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::DONT_CARE)
         .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+        .final_layout(final_layout)
         .build();
-    let color_attachments = [color_attachment];
+    let mut attachments = vec![color_attachment];
+    if let Some(depth_format) = depth_format {
+        let depth_attachment = vk::AttachmentDescription::builder()
+            .format(depth_format)
+            .samples(samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+        attachments.push(depth_attachment);
+    }
+    let resolve_attachment_index = attachments.len() as u32;
+    if multisampled {
+        attachments.push(resolve_attachment);
+    }
 
     let color_attachment_ref = vk::AttachmentReference::builder()
         .attachment(0)
         .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
         .build();
     let color_attachment_refs = [color_attachment_ref];
-
-    let subpass = vk::SubpassDescription::builder()
-        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-        .color_attachments(&color_attachment_refs)
+    let depth_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(1)
+        .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
         .build();
-    let subpasses = [subpass];
+    let resolve_attachment_ref = vk::AttachmentReference::builder()
+        .attachment(resolve_attachment_index)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .build();
+    let resolve_attachment_refs = [resolve_attachment_ref];
 
+    let mut subpass = vk::SubpassDescription::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_attachment_refs);
+    if depth_format.is_some() {
+        subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
+    }
+    if multisampled {
+        subpass = subpass.resolve_attachments(&resolve_attachment_refs);
+    }
+    let subpasses = [subpass.build()];
+
+    // The depth attachment is shared by the frames in flight, so the depth tests of a frame wait
+    // for those of the previous one.
+    let (depth_stages, depth_writes, depth_access) = match depth_format {
+        Some(_) => (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        None => (
+            vk::PipelineStageFlags::empty(),
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::empty(),
+        ),
+    };
     let dependency = vk::SubpassDependency::builder()
         .src_subpass(vk::SUBPASS_EXTERNAL)
         .dst_subpass(0) // Reference to subpasses[0].
+        .src_stage_mask(
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::FRAGMENT_SHADER
+                | depth_stages,
+        )
+        .src_access_mask(depth_writes)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_stages)
+        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | depth_access)
+        .build();
+    let read_dependency = vk::SubpassDependency::builder()
+        .src_subpass(0)
+        .dst_subpass(vk::SUBPASS_EXTERNAL)
         .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-        .src_access_mask(vk::AccessFlags::empty())
-        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
         .build();
-    let dependencies = [dependency];
+    let dependencies = [dependency, read_dependency];
 
     let render_pass_info = vk::RenderPassCreateInfo::builder()
-        .attachments(&color_attachments)
+        .attachments(&attachments)
         .subpasses(&subpasses)
         .dependencies(&dependencies);
 
-    unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    let render_pass = unsafe { device.create_render_pass(&render_pass_info, None).unwrap() };
+    RenderPass::new(device, render_pass)
 }
 
 pub fn create_graphics_pipeline(
-    device: &ash::Device,
+    device: &Rc<Device>,
     swapchain_extent: vk::Extent2D,
     render_pass: vk::RenderPass,
     descriptor_set_layout: vk::DescriptorSetLayout,
-) -> (vk::Pipeline, vk::PipelineLayout) {
-    let out_dir = PathBuf::from("src/vkrs/shaders");
-    let vertex_shader_code = shader::read_shader_file(&out_dir.join("shader.vert.spv"));
-    let fragment_shader_code = shader::read_shader_file(&out_dir.join("shader.frag.spv"));
+    samples: vk::SampleCountFlags,
+) -> (Pipeline, PipelineLayout) {
+    let vertex_shader_code = shader::read_spirv(include_bytes!("shaders/shader.vert.spv"));
+    let fragment_shader_code = shader::read_spirv(include_bytes!("shaders/shader.frag.spv"));
 
     let vertex_shader_module = shader::create_shader_module(device, &vertex_shader_code);
     let fragment_shader_module = shader::create_shader_module(device, &fragment_shader_code);
+    vertex_shader_module.set_name("shader.vert");
+    fragment_shader_module.set_name("shader.frag");
 
     let shader_entry_point = CString::new("main").unwrap();
     let vertex_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::VERTEX)
-        .module(vertex_shader_module)
+        .module(vertex_shader_module.handle())
         .name(&shader_entry_point)
         .build();
     let fragment_shader_stage_info = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::FRAGMENT)
-        .module(fragment_shader_module)
+        .module(fragment_shader_module.handle())
         .name(&shader_entry_point)
         .build();
 
@@ -391,11 +728,19 @@ pub fn create_graphics_pipeline(
     // Multisampling.
     let multisampling = vk::PipelineMultisampleStateCreateInfo::builder()
         .sample_shading_enable(false)
-        .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+        .rasterization_samples(samples)
         .min_sample_shading(1.0)
         .alpha_to_coverage_enable(false)
         .alpha_to_one_enable(false);
 
+    // Depth testing, nearer fragments have a smaller depth.
+    let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(true)
+        .depth_write_enable(true)
+        .depth_compare_op(vk::CompareOp::LESS)
+        .depth_bounds_test_enable(false)
+        .stencil_test_enable(false);
+
     // Color blending.
     let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
         .color_write_mask(vk::ColorComponentFlags::RGBA)
@@ -416,12 +761,21 @@ pub fn create_graphics_pipeline(
 
     // Pipeline layout.
     let set_layouts = [descriptor_set_layout];
-    let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+    let push_constant_ranges = [vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+        .offset(0)
+        .size(size_of::<ObjectPushConstants>() as u32)
+        .build()];
+    let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(&push_constant_ranges);
     let pipeline_layout = unsafe {
         device
             .create_pipeline_layout(&pipeline_layout_info, None)
             .unwrap()
     };
+    let pipeline_layout = PipelineLayout::new(device, pipeline_layout);
+    pipeline_layout.set_name("scene pipeline layout");
 
     let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
         .stages(&shader_stages)
@@ -430,8 +784,9 @@ pub fn create_graphics_pipeline(
         .viewport_state(&viewport_state)
         .rasterization_state(&rasterizer)
         .multisample_state(&multisampling)
+        .depth_stencil_state(&depth_stencil)
         .color_blend_state(&color_blending)
-        .layout(pipeline_layout)
+        .layout(pipeline_layout.handle())
         .render_pass(render_pass)
         .subpass(0)
         .build();
@@ -442,45 +797,53 @@ pub fn create_graphics_pipeline(
             .unwrap()[0]
     };
 
-    unsafe {
-        device.destroy_shader_module(vertex_shader_module, None);
-        device.destroy_shader_module(fragment_shader_module, None);
-    }
+    let graphics_pipeline = Pipeline::new(device, graphics_pipeline);
+    graphics_pipeline.set_name("scene pipeline");
 
+    // The shader modules are destroyed when they go out of scope, they are no longer needed once
+    // the pipeline has been created.
     (graphics_pipeline, pipeline_layout)
 }
 
 pub fn create_framebuffers(
-    device: &ash::Device,
-    swapchain_image_views: &[vk::ImageView],
+    device: &Rc<Device>,
+    swapchain_image_views: &[ImageView],
     render_pass: vk::RenderPass,
     swapchain_extent: vk::Extent2D,
-) -> Vec<vk::Framebuffer> {
+) -> Vec<Framebuffer> {
     swapchain_image_views
         .iter()
-        .map(|view| [*view])
-        .map(|attachments| {
-            let framebuffer_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(render_pass)
-                .attachments(&attachments)
-                .width(swapchain_extent.width)
-                .height(swapchain_extent.height)
-                .layers(1);
-            unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
-        })
+        .map(|view| create_framebuffer(device, &[view.handle()], render_pass, swapchain_extent))
         .collect::<Vec<_>>()
 }
 
+pub fn create_framebuffer(
+    device: &Rc<Device>,
+    attachments: &[vk::ImageView],
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+) -> Framebuffer {
+    let framebuffer_info = vk::FramebufferCreateInfo::builder()
+        .render_pass(render_pass)
+        .attachments(attachments)
+        .width(extent.width)
+        .height(extent.height)
+        .layers(1);
+    let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+    Framebuffer::new(device, framebuffer)
+}
+
 pub fn create_command_pool(
-    device: &ash::Device,
+    device: &Rc<Device>,
     command_pool_create_flags: vk::CommandPoolCreateFlags,
     queue_family_indices: &QueueFamilyIndices,
-) -> vk::CommandPool {
+) -> CommandPool {
     let pool_info = vk::CommandPoolCreateInfo::builder()
         .flags(command_pool_create_flags)
         .queue_family_index(queue_family_indices.graphics_family.unwrap());
 
-    unsafe { device.create_command_pool(&pool_info, None).unwrap() }
+    let command_pool = unsafe { device.create_command_pool(&pool_info, None).unwrap() };
+    CommandPool::new(device, command_pool)
 }
 
 fn find_memory_type(
@@ -499,13 +862,13 @@ fn find_memory_type(
     panic!("Failed to find a suitable memory type.")
 }
 
-fn create_buffer(
-    device: &ash::Device,
+pub fn create_buffer(
+    device: &Rc<Device>,
     device_memory_properties: vk::PhysicalDeviceMemoryProperties,
     size: vk::DeviceSize,
     usage_flags: vk::BufferUsageFlags,
     memory_property_flags: vk::MemoryPropertyFlags,
-) -> (vk::Buffer, vk::DeviceMemory, vk::DeviceSize) {
+) -> Buffer {
     let buffer_info = vk::BufferCreateInfo::builder()
         .size(size)
         .usage(usage_flags)
@@ -529,157 +892,107 @@ fn create_buffer(
     let buffer_memory = unsafe {
         device
             .allocate_memory(&alloc_info, None)
-            .expect("Failed to allocate buffer memory.")
+            .unwrap_or_else(|error| {
+                device.destroy_buffer(buffer, None);
+                panic!("Failed to allocate buffer memory: {:?}", error)
+            })
     };
+    let buffer = Buffer::new(device, buffer, buffer_memory, memory_requirements.size);
 
     unsafe {
         device
-            .bind_buffer_memory(buffer, buffer_memory, 0)
+            .bind_buffer_memory(buffer.handle(), buffer.memory(), 0)
             .expect("Failed to bind buffer memory.");
     }
 
-    (buffer, buffer_memory, memory_requirements.size)
+    buffer
 }
 
-fn copy_buffer(
-    device: &ash::Device,
-    command_pool: vk::CommandPool,
-    transfer_queue: vk::Queue,
-    src: vk::Buffer,
-    dst: vk::Buffer,
-    size: vk::DeviceSize,
-) {
-    let command_buffers = create_command_buffers(device, command_pool, 1);
-    let command_buffer = command_buffers[0];
-
-    let begin_info =
-        vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
-    unsafe {
-        device
-            .begin_command_buffer(command_buffer, &begin_info)
-            .unwrap();
-    }
-
-    let copy_region = vk::BufferCopy::builder()
-        .src_offset(0)
-        .dst_offset(0)
-        .size(size)
-        .build();
-    let regions = [copy_region];
-    unsafe { device.cmd_copy_buffer(command_buffer, src, dst, &regions) }
-
-    unsafe {
-        device.end_command_buffer(command_buffer).unwrap();
-    }
-
-    let submit_info = vk::SubmitInfo::builder()
-        .command_buffers(&command_buffers)
-        .build();
-    let submit_infos = [submit_info];
-    unsafe {
-        device
-            .queue_submit(transfer_queue, &submit_infos, vk::Fence::null())
-            .unwrap();
-        device.queue_wait_idle(transfer_queue).unwrap();
-    }
-
-    unsafe {
-        device.free_command_buffers(command_pool, &command_buffers);
-    }
-}
-
-fn create_device_local_buffer_with_data<A, T: Copy>(
-    device: &ash::Device,
+pub fn create_image(
+    device: &Rc<Device>,
     device_memory_properties: vk::PhysicalDeviceMemoryProperties,
-    command_pool: vk::CommandPool,
-    transfer_queue: vk::Queue,
-    buffer_usage_flags: vk::BufferUsageFlags,
-    data: &[T],
-) -> (vk::Buffer, vk::DeviceMemory) {
-    let buffer_size = (size_of::<T>() * data.len()) as vk::DeviceSize;
-
-    // TODO(lovew): Instead of creating a buffer here we could have implemented a memory allocator
-    // that we would request memory from, and it would give us a chunk of memory that was bound to
-    // a buffer and mapped to some host memory.
-    let (staging_buffer, staging_buffer_memory, staging_memory_size) = create_buffer(
-        device,
-        device_memory_properties,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_SRC,
-        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-    );
-
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to map staging buffer memory.");
-        let mut align = ash::util::Align::new(data_ptr, align_of::<A>() as _, staging_memory_size);
-        align.copy_from_slice(data);
-        device.unmap_memory(staging_buffer_memory);
-    }
+    extent: vk::Extent2D,
+    format: vk::Format,
+    usage_flags: vk::ImageUsageFlags,
+    samples: vk::SampleCountFlags,
+) -> Image {
+    let image_info = vk::ImageCreateInfo::builder()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        })
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(samples)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage_flags)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+    let image = unsafe {
+        device
+            .create_image(&image_info, None)
+            .expect("Failed to create image.")
+    };
 
-    let (buffer, buffer_memory, _) = create_buffer(
-        device,
+    let memory_requirements = unsafe { device.get_image_memory_requirements(image) };
+    let memory_type = find_memory_type(
+        memory_requirements,
         device_memory_properties,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_DST | buffer_usage_flags,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
     );
 
-    copy_buffer(
-        device,
-        command_pool,
-        transfer_queue,
-        staging_buffer,
-        buffer,
-        buffer_size,
-    );
+    let alloc_info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(memory_requirements.size)
+        .memory_type_index(memory_type);
+    let image_memory = unsafe {
+        device
+            .allocate_memory(&alloc_info, None)
+            .unwrap_or_else(|error| {
+                device.destroy_image(image, None);
+                panic!("Failed to allocate image memory: {:?}", error)
+            })
+    };
+    let image = Image::new(device, image, image_memory, memory_requirements.size);
 
     unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
+        device
+            .bind_image_memory(image.handle(), image.memory(), 0)
+            .expect("Failed to bind image memory.");
     }
 
-    (buffer, buffer_memory)
+    image
 }
 
-pub fn create_vertex_buffer(
-    device: &ash::Device,
-    device_memory_properties: vk::PhysicalDeviceMemoryProperties,
-    transfer_queue: vk::Queue,
-    command_pool: vk::CommandPool,
-    vertices: &[Vertex],
-) -> (vk::Buffer, vk::DeviceMemory) {
-    create_device_local_buffer_with_data::<f32, _>(
-        device,
-        device_memory_properties,
-        command_pool,
-        transfer_queue,
-        vk::BufferUsageFlags::VERTEX_BUFFER,
-        vertices,
-    )
-}
+/// Creates a view of the single mip level and array layer of `image`.
+pub fn create_image_view(
+    device: &Rc<Device>,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+) -> ImageView {
+    let create_info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .components(vk::ComponentMapping {
+            r: vk::ComponentSwizzle::IDENTITY,
+            g: vk::ComponentSwizzle::IDENTITY,
+            b: vk::ComponentSwizzle::IDENTITY,
+            a: vk::ComponentSwizzle::IDENTITY,
+        })
+        .subresource_range(vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        });
 
-pub fn create_index_buffer(
-    device: &ash::Device,
-    device_memory_properties: vk::PhysicalDeviceMemoryProperties,
-    transfer_queue: vk::Queue,
-    command_pool: vk::CommandPool,
-    indices: &[u16],
-) -> (vk::Buffer, vk::DeviceMemory) {
-    create_device_local_buffer_with_data::<u16, _>(
-        device,
-        device_memory_properties,
-        command_pool,
-        transfer_queue,
-        vk::BufferUsageFlags::INDEX_BUFFER,
-        indices,
-    )
+    let image_view = unsafe { device.create_image_view(&create_info, None).unwrap() };
+    ImageView::new(device, image_view)
 }
 
 pub fn create_command_buffers(
@@ -695,32 +1008,28 @@ pub fn create_command_buffers(
     unsafe { device.allocate_command_buffers(&alloc_info).unwrap() }
 }
 
-pub fn record_command_buffer(
-    device: &ash::Device,
+/// Begins `render_pass`, created by [`create_render_pass`] with a depth attachment, with inline
+/// contents, clearing its color attachment to black and its depth attachment to the far plane.
+pub fn begin_render_pass(
+    device: &Device,
     command_buffer: vk::CommandBuffer,
     render_pass: vk::RenderPass,
     framebuffer: vk::Framebuffer,
     swapchain_extent: vk::Extent2D,
-    graphics_pipeline: vk::Pipeline,
-    vertex_buffer: vk::Buffer,
-    index_buffer: vk::Buffer,
-    num_vertex_indices: u32,
-    pipeline_layout: vk::PipelineLayout,
-    descriptor_set: vk::DescriptorSet,
 ) {
-    // Begin the command buffer.
-    let begin_info = vk::CommandBufferBeginInfo::builder().build();
-    unsafe {
-        device
-            .begin_command_buffer(command_buffer, &begin_info)
-            .unwrap()
-    };
-
-    let clear_values = [vk::ClearValue {
-        color: vk::ClearColorValue {
-            float32: [0.0, 0.0, 0.0, 1.0],
+    let clear_values = [
+        vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        },
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0,
+            },
         },
-    }];
+    ];
     let render_pass_info = vk::RenderPassBeginInfo::builder()
         .render_pass(render_pass)
         .framebuffer(framebuffer)
@@ -735,19 +1044,37 @@ pub fn record_command_buffer(
             &render_pass_info,
             vk::SubpassContents::INLINE,
         );
+    }
+}
 
+/// A mesh drawn by [`record_scene_pass`].
+pub struct SceneDraw {
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub num_indices: u32,
+    pub push_constants: ObjectPushConstants,
+}
+
+/// Records the scene geometry, inside the scene render pass started with [`begin_render_pass`].
+pub fn record_scene_pass(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    graphics_pipeline: vk::Pipeline,
+    draws: &[SceneDraw],
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_set: vk::DescriptorSet,
+) -> DrawStats {
+    // Without anything to draw, only the clear is recorded.
+    if draws.is_empty() {
+        return DrawStats::default();
+    }
+    unsafe {
         device.cmd_bind_pipeline(
             command_buffer,
             vk::PipelineBindPoint::GRAPHICS,
             graphics_pipeline,
         );
 
-        let vertex_buffers = [vertex_buffer];
-        let offsets = [0];
-        device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
-
-        device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, vk::IndexType::UINT16);
-
         let descriptor_sets = [descriptor_set];
         let dynamic_offsets = [];
         device.cmd_bind_descriptor_sets(
@@ -758,21 +1085,50 @@ pub fn record_command_buffer(
             &descriptor_sets,
             &dynamic_offsets,
         );
-        device.cmd_draw_indexed(command_buffer, num_vertex_indices, 1, 0, 0, 0);
-
-        device.cmd_end_render_pass(command_buffer);
-
-        device.end_command_buffer(command_buffer).unwrap();
+    }
+    let mut triangles = 0;
+    for draw in draws {
+        let push_constants = unsafe {
+            slice::from_raw_parts(
+                &draw.push_constants as *const ObjectPushConstants as *const u8,
+                size_of::<ObjectPushConstants>(),
+            )
+        };
+        unsafe {
+            let vertex_buffers = [draw.vertex_buffer];
+            let offsets = [0];
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
+            device.cmd_bind_index_buffer(
+                command_buffer,
+                draw.index_buffer,
+                0,
+                vk::IndexType::UINT16,
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                pipeline_layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                push_constants,
+            );
+            device.cmd_draw_indexed(command_buffer, draw.num_indices, 1, 0, 0, 0);
+        }
+        triangles += draw.num_indices as u64 / 3;
+    }
+    DrawStats {
+        draw_calls: draws.len() as u32,
+        triangles,
+        pipeline_binds: 1,
+        descriptor_binds: 1,
     }
 }
 
 pub fn create_sync_objects(
-    device: &ash::Device,
+    device: &Rc<Device>,
     max_frames_in_flight: u32,
-) -> (Vec<vk::Semaphore>, Vec<vk::Semaphore>, Vec<vk::Fence>) {
+) -> (Vec<Semaphore>, Vec<Semaphore>) {
     let mut image_available_semaphores = Vec::new();
     let mut render_finished_semaphores = Vec::new();
-    let mut in_flight_fences = Vec::new();
 
     for _ in 0..max_frames_in_flight {
         let image_available_semaphore = {
@@ -783,7 +1139,7 @@ pub fn create_sync_objects(
                     .expect("Failed to create semaphore.")
             }
         };
-        image_available_semaphores.push(image_available_semaphore);
+        image_available_semaphores.push(Semaphore::new(device, image_available_semaphore));
 
         let render_finished_semaphore = {
             let semaphore_info = vk::SemaphoreCreateInfo::builder();
@@ -793,69 +1149,54 @@ pub fn create_sync_objects(
                     .expect("Failed to create semaphore.")
             }
         };
-        render_finished_semaphores.push(render_finished_semaphore);
-
-        let in_flight_fence = {
-            let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
-            unsafe {
-                device
-                    .create_fence(&fence_info, None)
-                    .expect("Failed to create fence.")
-            }
-        };
-        in_flight_fences.push(in_flight_fence);
+        render_finished_semaphores.push(Semaphore::new(device, render_finished_semaphore));
     }
 
-    (
-        image_available_semaphores,
-        render_finished_semaphores,
-        in_flight_fences,
-    )
+    (image_available_semaphores, render_finished_semaphores)
 }
 
-pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
+pub fn create_descriptor_set_layout(device: &Rc<Device>) -> DescriptorSetLayout {
     let ubo_layout_binding = vk::DescriptorSetLayoutBinding::builder()
         .binding(0)
         .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
         .descriptor_count(1)
-        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        // The fragment shader reads the lights.
+        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
         .build();
     let bindings = [ubo_layout_binding];
     let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
 
-    unsafe {
+    let layout = unsafe {
         device
             .create_descriptor_set_layout(&layout_info, None)
             .expect("Failed to create descriptor set layout.")
-    }
+    };
+    let layout = DescriptorSetLayout::new(device, layout);
+    layout.set_name("scene descriptor set layout");
+    layout
 }
 
 pub fn create_uniform_buffers(
-    device: &ash::Device,
+    device: &Rc<Device>,
     memory_properties: vk::PhysicalDeviceMemoryProperties,
     num_buffers: u32,
-) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
+) -> Vec<Buffer> {
     let buffer_size = size_of::<UniformBufferObject>() as vk::DeviceSize;
 
-    let mut buffers = Vec::new();
-    let mut buffer_memories = Vec::new();
-
-    for _ in 0..num_buffers {
-        let (buffer, buffer_memory, _) = create_buffer(
-            device,
-            memory_properties,
-            buffer_size,
-            vk::BufferUsageFlags::UNIFORM_BUFFER,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        );
-        buffers.push(buffer);
-        buffer_memories.push(buffer_memory);
-    }
-
-    (buffers, buffer_memories)
+    (0..num_buffers)
+        .map(|_| {
+            create_buffer(
+                device,
+                memory_properties,
+                buffer_size,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            )
+        })
+        .collect()
 }
 
-pub fn create_descriptor_pool(device: &ash::Device, num_descriptors: u32) -> vk::DescriptorPool {
+pub fn create_descriptor_pool(device: &Rc<Device>, num_descriptors: u32) -> DescriptorPool {
     let pool_size = vk::DescriptorPoolSize::builder()
         .ty(vk::DescriptorType::UNIFORM_BUFFER)
         .descriptor_count(num_descriptors)
@@ -866,11 +1207,12 @@ pub fn create_descriptor_pool(device: &ash::Device, num_descriptors: u32) -> vk:
         .pool_sizes(&pool_sizes)
         .max_sets(num_descriptors);
 
-    unsafe {
+    let descriptor_pool = unsafe {
         device
             .create_descriptor_pool(&pool_info, None)
             .expect("Failed to create descriptor pool.")
-    }
+    };
+    DescriptorPool::new(device, descriptor_pool)
 }
 
 pub fn create_descriptor_sets(
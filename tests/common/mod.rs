@@ -0,0 +1,30 @@
+//! Helpers shared by the integration tests, which render with a real device and are skipped on
+//! machines without one.
+
+use ash::vk;
+
+/// Whether a Vulkan loader and at least one physical device are available. Prints why the
+/// calling test is skipped when they are not.
+pub fn vulkan_available() -> bool {
+    let entry = match unsafe { ash::Entry::load() } {
+        Ok(entry) => entry,
+        Err(err) => {
+            eprintln!("Skipping, no Vulkan loader: {}", err);
+            return false;
+        }
+    };
+    let create_info = vk::InstanceCreateInfo::builder();
+    let instance = match unsafe { entry.create_instance(&create_info, None) } {
+        Ok(instance) => instance,
+        Err(err) => {
+            eprintln!("Skipping, failed to create a Vulkan instance: {}", err);
+            return false;
+        }
+    };
+    let num_devices = unsafe { instance.enumerate_physical_devices() }.map_or(0, |d| d.len());
+    unsafe { instance.destroy_instance(None) };
+    if num_devices == 0 {
+        eprintln!("Skipping, no Vulkan devices");
+    }
+    num_devices > 0
+}
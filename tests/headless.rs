@@ -0,0 +1,101 @@
+mod common;
+
+use vkrs::glam::{Mat4, Vec3, Vec4};
+use vkrs::{Camera, Material, MeshHandle, Renderer, RendererConfig, ValidationConfig, Vertex};
+
+const SIZE: [u32; 2] = [64, 64];
+
+fn config() -> RendererConfig {
+    RendererConfig {
+        validation: ValidationConfig {
+            enabled: false,
+            ..ValidationConfig::default()
+        },
+        ..RendererConfig::default()
+    }
+}
+
+fn camera() -> Camera {
+    Camera {
+        position: Vec3::new(0.0, 0.0, 2.0),
+        target: Vec3::ZERO,
+        up: Vec3::Y,
+        ..Camera::default()
+    }
+}
+
+/// A triangle at depth `z` that covers the center of the image, with both windings so that it is
+/// drawn regardless of which side is culled.
+fn add_triangle(renderer: &mut Renderer, name: &str, z: f32) -> MeshHandle {
+    let vertex = |x, y| Vertex {
+        pos: Vec3::new(x, y, z),
+        normal: Vec3::Z,
+        color: Vec3::ONE,
+    };
+    renderer.add_mesh(
+        name,
+        &[vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(0.0, 1.0)],
+        &[0, 1, 2, 0, 2, 1],
+    )
+}
+
+/// A material that shows `color` regardless of the lights.
+fn unlit(color: Vec3) -> Material {
+    Material {
+        base_color: Vec4::new(0.0, 0.0, 0.0, 1.0),
+        emissive: color,
+    }
+}
+
+fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let offset = ((y * SIZE[0] + x) * 4) as usize;
+    pixels[offset..offset + 4].try_into().unwrap()
+}
+
+#[test]
+fn first_frame_draws_a_freshly_added_mesh() {
+    if !common::vulkan_available() {
+        return;
+    }
+    let mut renderer = Renderer::headless("headless test", SIZE, &config());
+    renderer.set_camera(camera());
+    let mesh = add_triangle(&mut renderer, "triangle", 0.0);
+    let material = renderer.add_material(unlit(Vec3::ONE));
+    renderer.draw(mesh, material, Mat4::IDENTITY);
+    renderer.render();
+
+    let pixels = renderer.read_pixels().unwrap();
+    assert_eq!(pixels.len(), (SIZE[0] * SIZE[1] * 4) as usize);
+    assert_eq!(
+        pixel(&pixels, 0, 0)[..3],
+        [0, 0, 0],
+        "corner shows the clear color"
+    );
+    let center = pixel(&pixels, SIZE[0] / 2, SIZE[1] / 2);
+    assert_ne!(center[..3], [0, 0, 0], "center shows the triangle");
+}
+
+#[test]
+fn nearer_meshes_hide_farther_ones_drawn_after_them() {
+    if !common::vulkan_available() {
+        return;
+    }
+    let mut renderer = Renderer::headless("headless test", SIZE, &config());
+    renderer.set_camera(camera());
+    let near = add_triangle(&mut renderer, "near", 0.5);
+    let far = add_triangle(&mut renderer, "far", 0.0);
+    let red = renderer.add_material(unlit(Vec3::X));
+    let green = renderer.add_material(unlit(Vec3::Y));
+    renderer.draw(near, red, Mat4::IDENTITY);
+    renderer.draw(far, green, Mat4::IDENTITY);
+    renderer.render();
+
+    let pixels = renderer.read_pixels().unwrap();
+    let [r, g, _, _] = pixel(&pixels, SIZE[0] / 2, SIZE[1] / 2);
+    assert!(
+        r > 0 && g == 0,
+        "center shows the near triangle, got {} {}",
+        r,
+        g
+    );
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use vkrs::glam::{Mat4, Vec3};
+use vkrs::{Material, Renderer, RendererConfig, ValidationConfig, Vertex};
+
+use std::ffi::CStr;
+
+/// Whether the Khronos validation layer is installed, printing why the calling test is skipped
+/// when it is not.
+fn validation_layer_available() -> bool {
+    if !common::vulkan_available() {
+        return false;
+    }
+    let entry = unsafe { ash::Entry::load() }.unwrap();
+    let available = entry
+        .enumerate_instance_layer_properties()
+        .unwrap_or_default()
+        .iter()
+        .any(|layer| {
+            let name = unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) };
+            name.to_bytes() == b"VK_LAYER_KHRONOS_validation"
+        });
+    if !available {
+        eprintln!("Skipping, the validation layer is not installed");
+    }
+    available
+}
+
+#[test]
+fn headless_frame_emits_no_validation_errors() {
+    if !validation_layer_available() {
+        return;
+    }
+    let config = RendererConfig {
+        validation: ValidationConfig {
+            enabled: true,
+            synchronization: true,
+            fail_on_error: true,
+            ..ValidationConfig::default()
+        },
+        ..RendererConfig::default()
+    };
+    let mut renderer = Renderer::headless("validation test", [64, 64], &config);
+    let collector = renderer
+        .validation_collector()
+        .expect("fail_on_error collects validation errors");
+
+    let vertex = |x, y| Vertex {
+        pos: Vec3::new(x, y, 0.0),
+        normal: Vec3::Z,
+        color: Vec3::ONE,
+    };
+    let mesh = renderer.add_mesh(
+        "triangle",
+        &[vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(0.0, 1.0)],
+        &[0, 1, 2],
+    );
+    let material = renderer.add_material(Material::default());
+    renderer.draw(mesh, material, Mat4::IDENTITY);
+    renderer.render();
+    renderer.read_pixels().unwrap();
+    // Destroying the resources is validated too.
+    drop(renderer);
+
+    let errors = collector.errors();
+    assert!(
+        errors.is_empty(),
+        "{} validation errors, the first is: {}",
+        errors.len(),
+        errors[0].message
+    );
+}